@@ -0,0 +1,673 @@
+//! Plays whole games between snakes, without needing the official engine.
+//!
+//! Snakes can be servers reached over HTTP, or strategies running right here
+//! in the same process. Either way they're sent the same requests the official
+//! engine would send, and the game follows the same rules.
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use log::{debug, warn};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use reqwest::{blocking::Client, Url};
+
+use crate::{
+    fightsnake::{
+        constants::MAX_HEALTH,
+        models::{self, GameState, Movement, Ruleset},
+        types::{Coord, Direction},
+    },
+    strategies::{
+        strangle::{game::Game, SnakeID},
+        Strategy,
+    },
+};
+
+/// How a contestant decides on its moves.
+pub enum Player {
+    /// a snake server somewhere, asked over HTTP.
+    Remote(Url),
+    /// a strategy in this process. strategies keep track of the games they're
+    /// playing, so every contestant needs one of its own.
+    Local(Arc<dyn Strategy + Send + Sync>),
+}
+
+impl Player {
+    /// Sends `endpoint` a copy of `state`, returning the response if there is
+    /// one.
+    fn send(
+        url: &Url,
+        client: &Client,
+        endpoint: &str,
+        state: &GameState,
+    ) -> Result<reqwest::blocking::Response> {
+        let url = format!("{}/{endpoint}", url.as_str().trim_end_matches('/'));
+        Ok(client.post(url).json(state).send()?.error_for_status()?)
+    }
+
+    fn start(&self, client: &Client, state: &GameState) -> Result<()> {
+        match self {
+            Self::Remote(url) => Self::send(url, client, "start", state)?,
+            Self::Local(_) => return Ok(()),
+        };
+        Ok(())
+    }
+
+    fn choose(&self, client: &Client, state: GameState) -> Result<Movement> {
+        match self {
+            Self::Remote(url) => {
+                Ok(Self::send(url, client, "move", &state)?.json()?)
+            },
+            Self::Local(strategy) => Ok(Movement {
+                movement: strategy.get_movement(state)?,
+                shout:    None,
+            }),
+        }
+    }
+
+    fn end(&self, client: &Client, state: &GameState) -> Result<()> {
+        match self {
+            Self::Remote(url) => {
+                Self::send(url, client, "end", state)?;
+            },
+            Self::Local(strategy) => strategy.end(state),
+        }
+        Ok(())
+    }
+}
+
+/// A snake taking part in a game.
+pub struct Contestant {
+    pub name:   String,
+    /// squad games pit squads against each other. everyone else plays alone.
+    pub squad:  String,
+    pub player: Player,
+}
+
+impl Contestant {
+    #[must_use]
+    pub fn remote(name: impl Into<String>, url: Url) -> Self {
+        Self {
+            name:   name.into(),
+            squad:  String::new(),
+            player: Player::Remote(url),
+        }
+    }
+
+    #[must_use]
+    pub fn local(
+        name: impl Into<String>,
+        strategy: impl Strategy + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name:   name.into(),
+            squad:  String::new(),
+            player: Player::Local(Arc::new(strategy)),
+        }
+    }
+
+    /// Puts this contestant in `squad`, for squad games.
+    #[must_use]
+    pub fn with_squad(mut self, squad: impl Into<String>) -> Self {
+        self.squad = squad.into();
+        self
+    }
+}
+
+/// The kind of game to play.
+#[derive(Clone, Debug)]
+pub struct ArenaOptions {
+    pub width:     i64,
+    pub height:    i64,
+    pub ruleset:   Ruleset,
+    /// how many milliseconds each snake has to answer a request.
+    pub timeout:   u64,
+    /// where the starting positions and the food come from. the same seed and
+    /// the same moves always make the same game.
+    pub seed:      u64,
+    /// games still going after this many turns are called a draw.
+    pub max_turns: Option<u64>,
+}
+
+impl Default for ArenaOptions {
+    fn default() -> Self {
+        Self {
+            width:     11,
+            height:    11,
+            ruleset:   Ruleset {
+                name:     "standard".to_owned(),
+                version:  "v1".to_owned(),
+                settings: None,
+            },
+            timeout:   500,
+            seed:      0,
+            max_turns: None,
+        }
+    }
+}
+
+/// How a game went.
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    pub turns:        u64,
+    /// the contestants who won, by their position in the line-up. that's a
+    /// whole squad in squad games, and nobody at all for a draw or a solo
+    /// game.
+    pub winners:      Vec<usize>,
+    /// the turn each contestant was eliminated on, if they were.
+    pub eliminations: Vec<Option<u64>>,
+}
+
+impl Outcome {
+    /// How contestant `a` did against contestant `b`. Whoever lasted longer
+    /// did better, and going out on the same turn is a draw.
+    #[must_use]
+    pub fn versus(&self, a: usize, b: usize) -> Ordering {
+        let lasted = |id: usize| self.eliminations[id].unwrap_or(u64::MAX);
+        lasted(a).cmp(&lasted(b))
+    }
+}
+
+/// How a set of games went for one side.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Record {
+    pub wins:   u32,
+    pub draws:  u32,
+    pub losses: u32,
+}
+
+impl Record {
+    /// Counts one more game, going by how it compared for us.
+    pub const fn add(&mut self, result: Ordering) {
+        match result {
+            Ordering::Greater => self.wins += 1,
+            Ordering::Equal => self.draws += 1,
+            Ordering::Less => self.losses += 1,
+        }
+    }
+
+    /// The same games, from the other side.
+    #[must_use]
+    pub const fn flipped(self) -> Self {
+        Self {
+            wins:   self.losses,
+            draws:  self.draws,
+            losses: self.wins,
+        }
+    }
+
+    #[must_use]
+    pub const fn games(self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The share of the points won, counting a draw as half a win.
+    #[must_use]
+    pub fn score(self) -> f64 {
+        (f64::from(self.wins) + f64::from(self.draws) / 2.0)
+            / f64::from(self.games().max(1))
+    }
+
+    /// The standard error of [`Self::score`].
+    #[must_use]
+    pub fn error(self) -> f64 {
+        let score = self.score();
+        (score * (1.0 - score) / f64::from(self.games().max(1))).sqrt()
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "+{} ={} -{}", self.wins, self.draws, self.losses)
+    }
+}
+
+/// Board sizes that have set spawn points, rather than random ones.
+const FIXED_SIZES: [i64; 3] = [7, 11, 19];
+
+/// Sets up a new game for `snakes` snakes the way the official engine does.
+/// Snakes are named and identified by their number, and the first is `you`.
+///
+/// # Errors
+///
+/// Fails if there isn't room on the board for everyone, or if there are no
+/// snakes at all.
+pub fn new_game<R: Rng>(
+    snakes: usize,
+    options: &ArenaOptions,
+    rng: &mut R,
+) -> Result<GameState> {
+    let (width, height) = (options.width, options.height);
+    let fixed = width == height && FIXED_SIZES.contains(&width);
+
+    let heads = if fixed {
+        // the middle of every edge and every corner, one square in.
+        let (low, middle, high) = (1, (width - 1) / 2, width - 2);
+        let mut points = vec![
+            Coord { x: low, y: low },
+            Coord { x: low, y: middle },
+            Coord { x: low, y: high },
+            Coord { x: middle, y: low },
+            Coord { x: middle, y: high },
+            Coord { x: high, y: low },
+            Coord { x: high, y: middle },
+            Coord { x: high, y: high },
+        ];
+        points.shuffle(rng);
+        points
+    } else {
+        // snakes only start on even squares, so that no two can ever meet
+        // head-on with the same length.
+        let mut points: Vec<Coord> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| Coord { x, y }))
+            .filter(|c| (c.x + c.y) % 2 == 0)
+            .collect();
+        points.shuffle(rng);
+        points
+    };
+    if heads.len() < snakes {
+        return Err(eyre!("there's only room for {} snakes", heads.len()));
+    }
+    let heads = &heads[..snakes];
+
+    let food = if options.ruleset.name == "constrictor" {
+        vec![]
+    } else if fixed {
+        starting_food(width, height, heads, rng)
+    } else {
+        let mut free: Vec<Coord> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| Coord { x, y }))
+            .filter(|c| !heads.contains(c))
+            .collect();
+        free.shuffle(rng);
+        free.truncate(snakes);
+        free
+    };
+
+    let snakes: Vec<models::Snake> = heads
+        .iter()
+        .enumerate()
+        .map(|(index, &head)| models::Snake {
+            id: index.to_string(),
+            name: index.to_string(),
+            health: MAX_HEALTH,
+            body: [head; 3].into(),
+            latency: 0,
+            head,
+            length: 3,
+            shout: String::new(),
+            squad: String::new(),
+            customizations: None,
+        })
+        .collect();
+    let you = snakes
+        .first()
+        .cloned()
+        .ok_or_else(|| eyre!("a game needs at least one snake"))?;
+
+    Ok(GameState {
+        game: models::Game {
+            id:      format!("arena-{}", options.seed),
+            ruleset: options.ruleset.clone(),
+            map:     None,
+            source:  Some("arena".to_owned()),
+            timeout: options.timeout,
+        },
+        turn: 0,
+        board: models::Board {
+            height,
+            width,
+            food,
+            hazards: vec![],
+            snakes,
+        },
+        you,
+    })
+}
+
+/// A piece of food diagonally next to each snake, on the side away from the
+/// middle but never in a corner, and one more in the middle.
+fn starting_food<R: Rng>(
+    width: i64,
+    height: i64,
+    heads: &[Coord],
+    rng: &mut R,
+) -> Vec<Coord> {
+    let middle = Coord {
+        x: (width - 1) / 2,
+        y: (height - 1) / 2,
+    };
+    let corners = [
+        Coord { x: 0, y: 0 },
+        Coord {
+            x: 0,
+            y: height - 1,
+        },
+        Coord { x: width - 1, y: 0 },
+        Coord {
+            x: width - 1,
+            y: height - 1,
+        },
+    ];
+
+    let mut food: Vec<Coord> = vec![];
+    for head in heads {
+        let away = |p: i64, h: i64, m: i64| p < h && h < m || m < h && h < p;
+        let spots: Vec<Coord> = [(-1, -1), (-1, 1), (1, -1), (1, 1)]
+            .into_iter()
+            .map(|(dx, dy)| Coord {
+                x: head.x + dx,
+                y: head.y + dy,
+            })
+            .filter(|&c| {
+                c != middle
+                    && !food.contains(&c)
+                    && !corners.contains(&c)
+                    && (away(c.x, head.x, middle.x)
+                        || away(c.y, head.y, middle.y))
+            })
+            .collect();
+        if let Some(&spot) = spots.choose(rng) {
+            food.push(spot);
+        }
+    }
+
+    if !heads.contains(&middle) {
+        food.push(middle);
+    }
+    food
+}
+
+/// A game in progress, along with everything the contestants are told about
+/// it.
+struct Match<'a> {
+    contestants: &'a [Contestant],
+    client:      Client,
+    game:        Game,
+    /// the game as the contestants see it, apart from who `you` are.
+    state:       GameState,
+    /// every contestant as they were last seen, whether they're still in the
+    /// game or not.
+    snakes:      Vec<models::Snake>,
+    /// what each contestant did last, in case they don't answer next time.
+    last_moves:  Vec<Direction>,
+}
+
+impl Match<'_> {
+    /// Brings the state the contestants are sent up to date with the game.
+    fn update(&mut self, turn: u64) {
+        for alive in &self.game.snakes {
+            let snake = &mut self.snakes[alive.id];
+            snake.body.clone_from(&alive.body);
+            snake.health = alive.health;
+            snake.head = alive.body.front().copied().unwrap_or(snake.head);
+            snake.length = alive.body.len() as u64;
+        }
+
+        self.state.turn = turn;
+        self.state.board.food.clone_from(&self.game.food);
+        self.state.board.hazards.clone_from(&self.game.hazards);
+        self.state.board.snakes = self
+            .game
+            .snakes
+            .iter()
+            .map(|snake| self.snakes[snake.id].clone())
+            .collect();
+    }
+
+    /// The state as `id` sees it.
+    fn seen_by(&self, id: SnakeID) -> GameState {
+        GameState {
+            you: self.snakes[id].clone(),
+            ..self.state.clone()
+        }
+    }
+
+    /// Lets every contestant know the game has started.
+    fn start(&self) {
+        for (id, contestant) in self.contestants.iter().enumerate() {
+            if let Err(e) =
+                contestant.player.start(&self.client, &self.seen_by(id))
+            {
+                warn!("{} didn't start cleanly: {e}", contestant.name);
+            }
+        }
+    }
+
+    /// Asks every snake still in the game for their move, all at once.
+    fn ask(&mut self) -> Result<HashMap<SnakeID, Direction>> {
+        let answers = thread::scope(|scope| {
+            let mut asking = vec![];
+            for snake in &self.game.snakes {
+                let (id, state) = (snake.id, self.seen_by(snake.id));
+                let (player, client) =
+                    (&self.contestants[id].player, &self.client);
+                asking.push(scope.spawn(move || {
+                    let start = Instant::now();
+                    let answer = player.choose(client, state);
+                    (id, answer, start.elapsed())
+                }));
+            }
+            asking
+                .into_iter()
+                .map(|asking| {
+                    asking.join().map_err(|_| eyre!("a snake panicked"))
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        let mut moves = HashMap::new();
+        for (id, answer, latency) in answers {
+            let snake = &mut self.snakes[id];
+            snake.latency =
+                u64::try_from(latency.as_millis()).unwrap_or(u64::MAX);
+            match answer {
+                Ok(movement) => {
+                    self.last_moves[id] = movement.movement;
+                    snake.shout = movement.shout.unwrap_or_default();
+                },
+                Err(e) => {
+                    warn!(
+                        "{} didn't move on turn {}: {e}",
+                        snake.name, self.state.turn
+                    );
+                },
+            }
+            moves.insert(id, self.last_moves[id]);
+        }
+        Ok(moves)
+    }
+
+    /// Lets every contestant know the game is over, including the ones that
+    /// were eliminated along the way.
+    fn end(&self) {
+        for (id, contestant) in self.contestants.iter().enumerate() {
+            if let Err(e) =
+                contestant.player.end(&self.client, &self.seen_by(id))
+            {
+                warn!("{} didn't end cleanly: {e}", contestant.name);
+            }
+        }
+    }
+}
+
+/// Plays a game from start to finish between `contestants`, sending each of
+/// them the usual `/start`, `/move` and `/end` requests along the way.
+///
+/// Contestants that fail to answer in time, or answer with something that
+/// makes no sense, keep going the way they went last. That's up, if they
+/// haven't moved yet.
+///
+/// # Errors
+///
+/// Fails if the game can't be set up, for example if there isn't room for
+/// everyone on the board.
+pub fn play(
+    contestants: &[Contestant],
+    options: &ArenaOptions,
+) -> Result<Outcome> {
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut state = new_game(contestants.len(), options, &mut rng)?;
+    for (snake, contestant) in state.board.snakes.iter_mut().zip(contestants) {
+        snake.name.clone_from(&contestant.name);
+        snake.squad.clone_from(&contestant.squad);
+    }
+    state.you = state.board.snakes[0].clone();
+
+    // everyone is in the same place in the game as in the line-up, since the
+    // first snake is already `you`.
+    let mut arena = Match {
+        contestants,
+        client: Client::builder()
+            .timeout(Duration::from_millis(options.timeout))
+            .build()?,
+        game: Game::try_from(state.clone())?,
+        snakes: state.board.snakes.clone(),
+        state,
+        last_moves: vec![Direction::Up; contestants.len()],
+    };
+    let mut eliminations = vec![None; contestants.len()];
+
+    arena.start();
+
+    let mut turn = 0;
+    while !is_over(&arena.game)
+        && options.max_turns.is_none_or(|max| turn < max)
+    {
+        let moves = arena.ask()?;
+        let (next, deaths) = arena.game.step_standard(&moves, &mut rng)?;
+        arena.game = next;
+
+        turn += 1;
+        for id in deaths.keys() {
+            eliminations[*id] = Some(turn);
+        }
+        arena.update(turn);
+        debug!("turn {turn}:\n{}", arena.game);
+    }
+
+    arena.end();
+
+    let winners = if arena.game.multisnake && is_over(&arena.game) {
+        arena.game.snakes.iter().map(|snake| snake.id).collect()
+    } else {
+        vec![]
+    };
+
+    Ok(Outcome {
+        turns: turn,
+        winners,
+        eliminations,
+    })
+}
+
+/// Solo games go on until the snake dies. Everything else stops once there's
+/// only one team left.
+fn is_over(game: &Game) -> bool {
+    if game.multisnake {
+        game.is_over()
+    } else {
+        game.snakes.is_empty()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use color_eyre::Result;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{new_game, play, ArenaOptions, Contestant};
+    use crate::{
+        fightsnake::{
+            models::GameState,
+            types::{Coord, Direction},
+        },
+        strategies::Strategy,
+    };
+
+    /// Always goes the same way, no matter what.
+    struct Stubborn(Direction);
+
+    impl Strategy for Stubborn {
+        fn get_movement(&self, _game_state: GameState) -> Result<Direction> {
+            Ok(self.0)
+        }
+    }
+
+    /// Goes round and round in a little square.
+    struct Circler;
+
+    impl Strategy for Circler {
+        fn get_movement(&self, game_state: GameState) -> Result<Direction> {
+            let you = game_state.you;
+            let neck = you.body.get(1).copied().unwrap_or(you.head);
+            Ok(match Direction::between(neck, you.head) {
+                _ if neck == you.head => Direction::Up,
+                Some(Direction::Up) => Direction::Right,
+                Some(Direction::Right) => Direction::Down,
+                Some(Direction::Down) => Direction::Left,
+                _ => Direction::Up,
+            })
+        }
+    }
+
+    #[test]
+    fn snakes_start_apart_with_food_nearby() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let state =
+            new_game(4, &ArenaOptions::default(), &mut rng).expect("no game");
+
+        let heads: Vec<Coord> =
+            state.board.snakes.iter().map(|snake| snake.head).collect();
+        for (i, head) in heads.iter().enumerate() {
+            assert!(!heads[..i].contains(head));
+            assert!(state.board.food.iter().any(|food| {
+                (food.x - head.x).abs() == 1 && (food.y - head.y).abs() == 1
+            }));
+        }
+        assert!(state.board.food.contains(&Coord { x: 5, y: 5 }));
+        assert_eq!(state.you.id, state.board.snakes[0].id);
+    }
+
+    #[test]
+    fn games_play_out_to_the_end() {
+        let contestants = [
+            Contestant::local("up", Stubborn(Direction::Up)),
+            Contestant::local("circler", Circler),
+        ];
+        let outcome =
+            play(&contestants, &ArenaOptions::default()).expect("game failed");
+
+        assert_eq!(outcome.winners, [1]);
+        assert!(outcome.eliminations[0].is_some());
+        assert_eq!(outcome.eliminations[1], None);
+        assert_eq!(outcome.eliminations[0], Some(outcome.turns));
+    }
+
+    #[test]
+    fn games_can_be_cut_short() {
+        let contestants = [
+            Contestant::local("one", Circler),
+            Contestant::local("two", Circler),
+        ];
+        let outcome = play(
+            &contestants,
+            &ArenaOptions {
+                max_turns: Some(20),
+                ..ArenaOptions::default()
+            },
+        )
+        .expect("game failed");
+
+        assert_eq!(outcome.turns, 20);
+        assert!(outcome.winners.is_empty());
+    }
+}
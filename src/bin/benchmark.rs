@@ -0,0 +1,372 @@
+// compares the search algorithms on a few fixed duel positions, and measures
+// how quickly the search gets through nodes, and whether it allocates while
+// doing so.
+// run this with --release, debug builds trace every node of the search.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    num::NonZeroUsize,
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use battlesnake_doctor_strangle::{
+    fightsnake::models::GameState,
+    strategies::strangle::{
+        brain::{
+            alphabeta,
+            bigbrain,
+            deepen,
+            search,
+            Algorithm,
+            BigbrainOptions,
+            Moves,
+            SearchContext,
+            Solver,
+        },
+        game::{Game, Undo},
+        score_factors::Weights,
+        transposition::TranspositionTable,
+        ME,
+    },
+};
+use color_eyre::{eyre::eyre, Result};
+
+/// Counts every allocation the benchmark makes, so we can check that the
+/// search doesn't make any once it has warmed up.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const TIME_LIMIT: Duration = Duration::from_millis(400);
+const STEPS: u32 = 100_000;
+const FIXED_DEPTHS: [u64; 4] = [2, 3, 4, 5];
+const ALGORITHMS: [Algorithm; 4] = [
+    Algorithm::MaxN,
+    Algorithm::AlphaBeta,
+    Algorithm::Simultaneous(Solver::MaxMin),
+    Algorithm::Simultaneous(Solver::Mixed),
+];
+
+struct Position {
+    name:  &'static str,
+    state: &'static str,
+}
+
+const POSITIONS: [Position; 2] = [
+    Position {
+        name:  "opening",
+        state: r#"{
+            "game": {"id": "bench", "ruleset": {"name": "standard", "version": "v1"}, "timeout": 500},
+            "turn": 3,
+            "board": {
+                "width": 11, "height": 11,
+                "food": [{"x": 5, "y": 5}, {"x": 0, "y": 10}, {"x": 10, "y": 2}],
+                "hazards": [],
+                "snakes": [
+                    {"id": "us", "name": "us", "health": 97, "latency": "0", "shout": "", "squad": "", "length": 3,
+                     "head": {"x": 1, "y": 4},
+                     "body": [{"x": 1, "y": 4}, {"x": 1, "y": 3}, {"x": 1, "y": 2}]},
+                    {"id": "them", "name": "them", "health": 97, "latency": "0", "shout": "", "squad": "", "length": 3,
+                     "head": {"x": 9, "y": 6},
+                     "body": [{"x": 9, "y": 6}, {"x": 9, "y": 7}, {"x": 9, "y": 8}]}
+                ]
+            },
+            "you": {"id": "us", "name": "us", "health": 97, "latency": "0", "shout": "", "squad": "", "length": 3,
+                    "head": {"x": 1, "y": 4},
+                    "body": [{"x": 1, "y": 4}, {"x": 1, "y": 3}, {"x": 1, "y": 2}]}
+        }"#,
+    },
+    Position {
+        name:  "midgame",
+        state: r#"{
+            "game": {"id": "bench", "ruleset": {"name": "standard", "version": "v1"}, "timeout": 500},
+            "turn": 60,
+            "board": {
+                "width": 11, "height": 11,
+                "food": [{"x": 2, "y": 8}, {"x": 8, "y": 1}],
+                "hazards": [],
+                "snakes": [
+                    {"id": "us", "name": "us", "health": 71, "latency": "0", "shout": "", "squad": "", "length": 7,
+                     "head": {"x": 5, "y": 6},
+                     "body": [{"x": 5, "y": 6}, {"x": 5, "y": 5}, {"x": 4, "y": 5}, {"x": 3, "y": 5},
+                              {"x": 3, "y": 4}, {"x": 3, "y": 3}, {"x": 4, "y": 3}]},
+                    {"id": "them", "name": "them", "health": 58, "latency": "0", "shout": "", "squad": "", "length": 6,
+                     "head": {"x": 7, "y": 7},
+                     "body": [{"x": 7, "y": 7}, {"x": 7, "y": 6}, {"x": 7, "y": 5}, {"x": 7, "y": 4},
+                              {"x": 8, "y": 4}, {"x": 9, "y": 4}]}
+                ]
+            },
+            "you": {"id": "us", "name": "us", "health": 71, "latency": "0", "shout": "", "squad": "", "length": 7,
+                    "head": {"x": 5, "y": 6},
+                    "body": [{"x": 5, "y": 6}, {"x": 5, "y": 5}, {"x": 4, "y": 5}, {"x": 3, "y": 5},
+                             {"x": 3, "y": 4}, {"x": 3, "y": 3}, {"x": 4, "y": 3}]}
+        }"#,
+    },
+];
+
+fn fixed_depth(
+    game: &Game,
+    algorithm: Algorithm,
+    depth: u64,
+) -> Result<(String, u64, Duration)> {
+    let start = Instant::now();
+    let options = BigbrainOptions {
+        max_depth: depth,
+        time_limit: Duration::MAX,
+        algorithm,
+        threads: 1,
+        weights: Weights::DEFAULT,
+    };
+    let known_scores = TranspositionTable::default();
+    let mut ctx = SearchContext::new(&known_scores, start, &options, None);
+
+    let result = search(game, &mut ctx)?
+        .ok_or_else(|| eyre!("search without a time limit ran out of time"))?;
+    let direction = result
+        .direction
+        .map_or_else(|| "none".to_owned(), |d| d.to_string());
+
+    Ok((direction, ctx.nodes, start.elapsed()))
+}
+
+/// How many nodes alpha-beta would visit to a fixed depth if it let our first
+/// moves cut each other off too, rather than searching each of them in full so
+/// that every one gets an exact score.
+///
+/// Our moves are played by hand here, in the same order the search would try
+/// them, with alpha raised after each one.
+fn pruned_root(game: &Game, depth: u64) -> Result<u64> {
+    let options = BigbrainOptions {
+        max_depth:  depth,
+        time_limit: Duration::MAX,
+        algorithm:  Algorithm::AlphaBeta,
+        threads:    1,
+        weights:    Weights::DEFAULT,
+    };
+    let known_scores = TranspositionTable::default();
+    let mut ctx =
+        SearchContext::new(&known_scores, Instant::now(), &options, None);
+    let mut game = game.clone();
+    let mut moves = Moves::new();
+
+    let mut alpha = i64::MIN;
+    for direction in game.snakes[ME].possible_directions(&game.board) {
+        moves.insert(ME, direction);
+        let result =
+            alphabeta(&mut game, 1, 0, &mut moves, alpha, i64::MAX, &mut ctx)?
+                .ok_or_else(|| {
+                    eyre!("search without a time limit ran out of time")
+                })?;
+        if let Some(score) = result.scores.get(ME) {
+            alpha = alpha.max(score.calculate(result.depth, &options.weights));
+        }
+        ctx.discard(result);
+    }
+
+    // the root itself counts as a node too.
+    Ok(ctx.nodes + 1)
+}
+
+/// Searches to a fixed depth twice with the same context, and returns how
+/// many nodes the second search visited and how many allocations it made.
+///
+/// The first search warms the context's buffers up. The second gets a fresh
+/// transposition table, made before counting starts, so it scores every leaf
+/// again rather than finding them all in the table. The only allocation that
+/// should be left is the one for the root's choices, however many nodes there
+/// were. Only max-n and alpha-beta are meant to get by like this; the
+/// simultaneous search builds a matrix for every turn.
+fn allocations(
+    game: &Game,
+    algorithm: Algorithm,
+    depth: u64,
+) -> Result<(u64, u64)> {
+    let options = BigbrainOptions {
+        max_depth: depth,
+        time_limit: Duration::MAX,
+        algorithm,
+        threads: 1,
+        weights: Weights::DEFAULT,
+    };
+    let warm_up = TranspositionTable::default();
+    let counted = TranspositionTable::default();
+    let mut ctx = SearchContext::new(&warm_up, Instant::now(), &options, None);
+    let mut game = game.clone();
+    let mut moves = Moves::new();
+    let mut run = |ctx: &mut SearchContext| {
+        match algorithm {
+            Algorithm::MaxN => bigbrain(&mut game, ME, 0, &mut moves, ctx),
+            _ => {
+                alphabeta(&mut game, ME, 0, &mut moves, i64::MIN, i64::MAX, ctx)
+            },
+        }?
+        .ok_or_else(|| eyre!("search without a time limit ran out of time"))
+    };
+
+    let result = run(&mut ctx)?;
+    ctx.discard(result);
+    ctx.known_scores = &counted;
+    ctx.nodes = 0;
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let result = run(&mut ctx)?;
+    let allocated = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    ctx.discard(result);
+
+    Ok((ctx.nodes, allocated))
+}
+
+/// How many of `count` things were done per second.
+#[allow(clippy::cast_precision_loss)]
+fn per_second(count: u64, elapsed: Duration) -> f64 {
+    count as f64 / elapsed.as_secs_f64()
+}
+
+/// Plays the same turn over and over, first by stepping into a copy of the
+/// game, then by making and unmaking it in place. Returns how many turns per
+/// second each way managed.
+fn stepping(game: &Game) -> Result<(f64, f64)> {
+    let moves = game
+        .snakes
+        .iter()
+        .map(|snake| {
+            let direction = snake
+                .possible_directions(&game.board)
+                .first()
+                .copied()
+                .ok_or_else(|| eyre!("snake #{} can't move", snake.id))?;
+            Ok((snake.id, direction))
+        })
+        .collect::<Result<_>>()?;
+
+    let start = Instant::now();
+    for _ in 0..STEPS {
+        game.step(&moves)?;
+    }
+    let cloning = per_second(STEPS.into(), start.elapsed());
+
+    let mut game = game.clone();
+    let mut undo = Undo::default();
+    let start = Instant::now();
+    for _ in 0..STEPS {
+        game.make(&moves, &mut undo)?;
+        game.unmake(&mut undo);
+    }
+    let in_place = per_second(STEPS.into(), start.elapsed());
+
+    Ok((cloning, in_place))
+}
+
+fn timed(
+    game: &Game,
+    algorithm: Algorithm,
+    threads: usize,
+) -> Result<(u64, u64)> {
+    let (result, nodes) = deepen(
+        game,
+        &BigbrainOptions {
+            max_depth: u64::MAX,
+            time_limit: TIME_LIMIT,
+            algorithm,
+            threads,
+            weights: Weights::DEFAULT,
+        },
+        &TranspositionTable::default(),
+        Instant::now(),
+    )?;
+    Ok((result.depth, nodes))
+}
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let cores = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let thread_counts = if cores > 1 { vec![1, cores] } else { vec![1] };
+
+    for position in &POSITIONS {
+        let state: GameState = serde_json::from_str(position.state)?;
+        let game = Game::try_from(state)?;
+
+        println!("{}:", position.name);
+
+        for depth in FIXED_DEPTHS {
+            let (maxn_move, maxn_nodes, maxn_time) =
+                fixed_depth(&game, Algorithm::MaxN, depth)?;
+            let (ab_move, ab_nodes, ab_time) =
+                fixed_depth(&game, Algorithm::AlphaBeta, depth)?;
+            println!(
+                "  depth {depth}: max-n {maxn_nodes} nodes in {maxn_time:?} \
+                 ({maxn_move}, {:.0} nodes/s), alpha-beta {ab_nodes} nodes in \
+                 {ab_time:?} ({ab_move}, {:.0} nodes/s){}",
+                per_second(maxn_nodes, maxn_time),
+                per_second(ab_nodes, ab_time),
+                if maxn_move == ab_move {
+                    ""
+                } else {
+                    " - moves differ!"
+                }
+            );
+
+            let pruned = pruned_root(&game, depth)?;
+            println!(
+                "    exact scores for every first move cost alpha-beta {} \
+                 nodes over the {pruned} it needs with them pruned",
+                ab_nodes.saturating_sub(pruned)
+            );
+        }
+
+        let depth = FIXED_DEPTHS[FIXED_DEPTHS.len() - 1];
+        for algorithm in [Algorithm::MaxN, Algorithm::AlphaBeta] {
+            let (nodes, allocated) = allocations(&game, algorithm, depth)?;
+            println!(
+                "  {algorithm:?} to depth {depth} once warmed up: {allocated} \
+                 allocations over {nodes} nodes"
+            );
+        }
+
+        for algorithm in ALGORITHMS {
+            for &threads in &thread_counts {
+                let (depth, nodes) = timed(&game, algorithm, threads)?;
+                println!(
+                    "  {algorithm:?} in {TIME_LIMIT:?} on {threads} threads: \
+                     reached depth {depth} ({nodes} nodes, {:.0} nodes/s)",
+                    per_second(nodes, TIME_LIMIT)
+                );
+            }
+        }
+
+        let (cloning, in_place) = stepping(&game)?;
+        println!(
+            "  stepping: {cloning:.0} turns/s copying the game, {in_place:.0} \
+             turns/s making and unmaking in place"
+        );
+    }
+
+    Ok(())
+}
@@ -1,74 +1,115 @@
-use std::process::Command;
-
-use color_eyre::Result;
-use reqwest::Url;
-
-enum GameMode {
-    Solo,
-}
-
-impl ToString for GameMode {
-    fn to_string(&self) -> String {
-        match self {
-            Self::Solo => "solo".to_owned(),
-        }
-    }
+// plays a game between snakes, with no need for the official engine.
+//
+// every argument is a snake, written as `name=snake` or just `snake`. a snake
+// is either the URL of a snake server, or the name of one of our strategies to
+// play in this process (`strangle` or `mcts`). with no snakes at all, the snake
+// running locally on port 6502 plays alone.
+//
+// the rest is set up through the environment:
+//   GYM_WIDTH, GYM_HEIGHT  how big the board is
+//   GYM_RULESET            which rules to play by, for example `constrictor`
+//   GYM_TIMEOUT            how many milliseconds each snake has to move
+//   GYM_SEED               where the starting positions and food come from.
+//                          random if left out.
+//   GYM_MAX_TURNS          how long to play before calling it a draw
+//
+// set RUST_LOG=debug to watch the game turn by turn.
+
+use std::{env, fmt::Debug, str::FromStr, time::Duration};
+
+use battlesnake_doctor_strangle::{
+    arena::{play, ArenaOptions, Contestant},
+    fightsnake::models::Ruleset,
+    strategies::{Mcts, Strangle},
+};
+use color_eyre::{eyre::eyre, Result};
+
+/// Reads a setting from the environment, if it's there.
+fn setting<T: FromStr>(name: &str) -> Result<Option<T>>
+where
+    T::Err: Debug,
+{
+    env::var(name)
+        .ok()
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|e| eyre!("{name} must be a valid value: {e:?}"))
+        })
+        .transpose()
 }
 
-struct PlayOptions {
-    board_width:  u64,
-    board_height: u64,
-    mode:         GameMode,
-}
-
-struct Snake {
-    name: String,
-    url:  Url,
-}
-
-fn make_play_command(play_options: &PlayOptions, snakes: &[Snake]) -> Command {
-    let mut cmd = Command::new("battlesnake");
-    cmd.arg("play");
-
-    cmd.arg("--width");
-    cmd.arg(play_options.board_width.to_string());
-    cmd.arg("--height");
-    cmd.arg(play_options.board_height.to_string());
-
-    for snake in snakes {
-        cmd.arg("--name");
-        cmd.arg(&snake.name);
-
-        cmd.arg("--url");
-        cmd.arg(snake.url.to_string());
-    }
-
-    cmd.arg("--gametype");
-    cmd.arg(play_options.mode.to_string());
-
-    cmd.arg("--browser");
-
-    cmd
+/// Works out who's playing from an argument like `name=snake`.
+fn contestant(arg: &str) -> Result<Contestant> {
+    let (name, snake) = arg.split_once('=').unwrap_or((arg, arg));
+    // strategies in this process don't need to leave time for the network,
+    // and shouldn't steal time from their opponents by pondering.
+    Ok(match snake {
+        "strangle" => Contestant::local(
+            name,
+            Strangle::new(Duration::ZERO).without_pondering(),
+        ),
+        "mcts" => Contestant::local(name, Mcts::new(Duration::ZERO)),
+        url => Contestant::remote(name, url.parse()?),
+    })
 }
 
 fn main() -> Result<()> {
+    pretty_env_logger::init();
     color_eyre::install()?;
 
-    let snakes = &mut [Snake {
-        name: "🧙 doctor strangle".to_owned(),
-        url:  "http://localhost:6502".parse()?,
-    }];
-
-    let mut play = make_play_command(
-        &PlayOptions {
-            board_width:  11,
-            board_height: 11,
-            mode:         GameMode::Solo,
-        },
-        snakes,
+    let args: Vec<String> = env::args().skip(1).collect();
+    let contestants = if args.is_empty() {
+        vec![contestant("🧙 doctor strangle=http://localhost:6502")?]
+    } else {
+        args.iter()
+            .map(|arg| contestant(arg))
+            .collect::<Result<_>>()?
+    };
+
+    let defaults = ArenaOptions::default();
+    let options = ArenaOptions {
+        width:     setting("GYM_WIDTH")?.unwrap_or(defaults.width),
+        height:    setting("GYM_HEIGHT")?.unwrap_or(defaults.height),
+        ruleset:   setting("GYM_RULESET")?.map_or(defaults.ruleset, |name| {
+            Ruleset {
+                name,
+                version: "v1".to_owned(),
+                settings: None,
+            }
+        }),
+        timeout:   setting("GYM_TIMEOUT")?.unwrap_or(defaults.timeout),
+        seed:      setting("GYM_SEED")?.unwrap_or_else(rand::random),
+        max_turns: setting("GYM_MAX_TURNS")?,
+    };
+
+    println!(
+        "playing {} on a {}x{} board with seed {}",
+        options.ruleset.name, options.width, options.height, options.seed
     );
+    let outcome = play(&contestants, &options)?;
+
+    for (contestant, eliminated) in
+        contestants.iter().zip(&outcome.eliminations)
+    {
+        match eliminated {
+            Some(turn) => {
+                println!("{} was eliminated on turn {turn}", contestant.name);
+            },
+            None => println!("{} survived", contestant.name),
+        }
+    }
 
-    play.status()?;
+    let winners: Vec<&str> = outcome
+        .winners
+        .iter()
+        .map(|&winner| contestants[winner].name.as_str())
+        .collect();
+    if winners.is_empty() {
+        println!("nobody won after {} turns", outcome.turns);
+    } else {
+        println!("{} won after {} turns", winners.join(" & "), outcome.turns);
+    }
 
     Ok(())
 }
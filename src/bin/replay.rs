@@ -0,0 +1,126 @@
+// goes back over a turn from a recorded game, to find out why we moved the way
+// we did.
+//
+//   replay <replay file> <turn> [depth]
+//
+// the turn is searched to a fixed depth instead of for as long as time allows,
+// so it comes out the same every time. the depth defaults to however deep we
+// got when the turn was played for real. every move we could have made is
+// shown with the scores at the end of the line of play it led to. the weights
+// come from WEIGHTS_FILE or WEIGHTS, just like they do for the real snake.
+
+use std::{cmp::Reverse, env, time::Duration};
+
+use battlesnake_doctor_strangle::{
+    fightsnake::replay::Replay,
+    strategies::{
+        strangle::{
+            brain::{describe_choices, describe_variation},
+            score_factors::Weights,
+            snake_ids,
+        },
+        Strangle,
+    },
+};
+use color_eyre::{eyre::eyre, Result};
+
+/// how deep to search turns that didn't record a depth.
+const DEFAULT_DEPTH: u64 = 5;
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [path, turn, rest @ ..] = args.as_slice() else {
+        return Err(eyre!("usage: replay <replay file> <turn> [depth]"));
+    };
+    let turn: u64 = turn.parse()?;
+
+    let weights = Weights::from_env()?;
+
+    let replay = Replay::load(path)?;
+    let recorded = replay
+        .turn(turn)
+        .ok_or_else(|| eyre!("we weren't asked to move on turn {turn}"))?;
+    let depth = rest
+        .first()
+        .map(|depth| depth.parse())
+        .transpose()?
+        .or(recorded.depth)
+        .unwrap_or(DEFAULT_DEPTH);
+
+    let state = recorded.state.clone();
+    println!(
+        "turn {turn} of game {} ({}, {}x{}), searching to depth {depth}",
+        state.game.id,
+        state.game.ruleset.name,
+        state.board.width,
+        state.board.height
+    );
+    match (&recorded.response, &recorded.error) {
+        (Some(response), _) => println!(
+            "we moved {:?} after searching to depth {}",
+            response.movement,
+            recorded
+                .depth
+                .map_or_else(|| "unknown".to_owned(), |d| d.to_string())
+        ),
+        (None, Some(error)) => println!("we failed to move: {error}"),
+        (None, None) => println!("we didn't answer"),
+    }
+
+    let names: Vec<String> = snake_ids(&state)
+        .into_iter()
+        .map(|id| {
+            state
+                .board
+                .snakes
+                .iter()
+                .find(|snake| snake.id == id)
+                .map_or(id.clone(), |snake| format!("{} ({id})", snake.name))
+        })
+        .collect();
+
+    let strangle = Strangle::new(Duration::ZERO)
+        .without_pondering()
+        .with_weights(weights)
+        .with_max_depth(depth);
+    let result = strangle.think(state)?;
+
+    println!(
+        "\nnow we'd move {:?}, looking {} turns ahead",
+        result
+            .direction
+            .ok_or_else(|| eyre!("the search didn't pick a move"))?,
+        result.depth
+    );
+
+    println!("choices: {}", describe_choices(&result.choices));
+    println!(
+        "\nexpecting:\n{}",
+        describe_variation(&result.variation, turn, |snake_id| {
+            names
+                .get(snake_id)
+                .cloned()
+                .unwrap_or_else(|| "?".to_owned())
+        })
+    );
+
+    let mut choices: Vec<_> = result.choices.iter().collect();
+    choices.sort_by_key(|(_, choice)| Reverse(choice.score));
+    for (direction, choice) in choices {
+        println!(
+            "\n{direction} scores {} after {} turns:",
+            choice.score, choice.depth
+        );
+        for score in choice.scores.iter() {
+            println!(
+                "{} scores {}\n{score}",
+                names.get(score.snake_id).map_or("?", String::as_str),
+                score.calculate(choice.depth, &weights)
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -1,12 +1,22 @@
+use std::{
+    collections::HashMap,
+    env,
+    num::NonZeroUsize,
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
 use battlesnake_doctor_strangle::{
     fightsnake::{
         models::{GameState, Movement, Status},
+        replay::{Entry, Recorder, Turn},
         types::{APIVersion, Head, Tail},
     },
-    strategies::{Strangle, Strategy},
+    strategies::{strangle::score_factors::Weights, Mcts, Strangle, Strategy},
 };
-use color_eyre::Result;
-use log::{error, info};
+use color_eyre::{eyre::eyre, Result};
+use log::{error, info, warn};
 use warp::{http::Method, Filter};
 
 const NAME: &str = env!("CARGO_PKG_NAME");
@@ -28,6 +38,85 @@ async fn main() -> Result<()> {
     #[cfg(not(debug_assertions))]
     info!("running in release mode");
 
+    let network_margin = env::var("NETWORK_MARGIN_MS")
+        .ok()
+        .map(|ms| ms.parse())
+        .transpose()?
+        .map_or(Strangle::DEFAULT_NETWORK_MARGIN, Duration::from_millis);
+    info!("leaving at least {network_margin:?} for the network");
+
+    let pondering = env::var("PONDER")
+        .ok()
+        .map(|ponder| ponder.parse())
+        .transpose()?
+        .unwrap_or(true);
+
+    let threads = env::var("THREADS")
+        .ok()
+        .map(|threads| threads.parse())
+        .transpose()?
+        .unwrap_or_else(|| {
+            thread::available_parallelism().map_or(1, NonZeroUsize::get)
+        });
+
+    let weights = Weights::from_env()?;
+    let version = format!("{VERSION}+weights.{}", weights.fingerprint());
+    info!("judging positions with {weights:?}");
+
+    let name = env::var("STRATEGY").unwrap_or_else(|_| "strangle".to_owned());
+    let strategy: Arc<dyn Strategy + Send + Sync> = match name.as_str() {
+        "strangle" => {
+            let strangle = Strangle::new(network_margin)
+                .with_threads(threads)
+                .with_weights(weights);
+            if pondering {
+                Arc::new(strangle)
+            } else {
+                Arc::new(strangle.without_pondering())
+            }
+        },
+        "mcts" => Arc::new(Mcts::new(network_margin).with_weights(weights)),
+        _ => return Err(eyre!("unknown strategy {name}")),
+    };
+    info!(
+        "playing with the {name} strategy (pondering: {pondering}, threads: \
+         {threads}, version: {version})"
+    );
+
+    // every game gets a replay file, unless recording is turned off. replays
+    // are written on their own thread, so that writing them never holds up a
+    // reply.
+    let recording = env::var("RECORD_REPLAYS")
+        .ok()
+        .map(|record| record.parse())
+        .transpose()?
+        .unwrap_or(true);
+    let entries = if recording {
+        let replays = env::var("REPLAY_DIR")
+            .unwrap_or_else(|_| Recorder::DEFAULT_DIRECTORY.to_owned());
+        info!("recording games into {replays}");
+        let recorder = Recorder::new(replays)?;
+        let (sender, receiver) = mpsc::channel::<Entry>();
+        thread::spawn(move || {
+            for entry in receiver {
+                if let Err(e) = recorder.record(&entry) {
+                    warn!("failed to record a replay: {e}");
+                }
+            }
+        });
+        Some(sender)
+    } else {
+        info!("not recording games");
+        None
+    };
+    let record = move |entry: Entry| {
+        if let Some(entries) = &entries
+            && entries.send(entry).is_err()
+        {
+            warn!("the replay writer has stopped");
+        }
+    };
+
     let cors = warp::cors()
         .allow_method(Method::GET)
         .allow_method(Method::POST)
@@ -36,41 +125,91 @@ async fn main() -> Result<()> {
 
     let logging = warp::log(NAME);
 
-    let healthz = warp::get().and(warp::path::end().map(|| {
+    let healthz = warp::get().and(warp::path::end().map(move || {
         warp::reply::json(&Status {
             apiversion: APIVersion::One,
             author:     AUTHOR.to_owned(),
             color:      "#AB4377".to_owned(),
             head:       Head::TransRightsScarf,
             tail:       Tail::MysticMoon,
-            version:    VERSION.to_owned(),
+            version:    version.clone(),
         })
     }));
 
+    let start_record = record.clone();
     let start = warp::post()
         .and(warp::path("start"))
         .and(warp::body::json())
-        .map(|_state: GameState| "".to_owned());
+        .map(move |state: GameState| {
+            start_record(Entry::Start { state });
+            "".to_owned()
+        });
 
+    let move_strategy = Arc::clone(&strategy);
+    let move_record = record.clone();
     let do_move = warp::post()
         .and(warp::path("move"))
         .and(warp::body::json())
-        .and_then(|game_state: GameState| async move {
-            Strangle
-                .get_movement(game_state)
-                .map(|movement| {
-                    warp::reply::json(&Movement {
-                        movement,
-                        shout: None,
-                    })
-                })
-                .map_err(|e| {
-                    error!("failed to get move: {}", e);
-                    warp::reject::custom(InternalError)
-                })
+        .and_then(move |game_state: GameState| {
+            let strategy = Arc::clone(&move_strategy);
+            let record = move_record.clone();
+            async move {
+                let started = Instant::now();
+                let decision = strategy.decide(game_state.clone());
+                let elapsed_ms = u64::try_from(started.elapsed().as_millis())
+                    .unwrap_or(u64::MAX);
+
+                let (turn, reply) = match decision {
+                    Ok(decision) => {
+                        let movement = Movement {
+                            movement: decision.movement,
+                            shout:    None,
+                        };
+                        let reply = warp::reply::json(&movement);
+                        let turn = Turn {
+                            state: game_state,
+                            response: Some(movement),
+                            error: None,
+                            depth: decision.depth,
+                            scores: decision.scores,
+                            elapsed_ms,
+                        };
+                        (turn, Ok(reply))
+                    },
+                    Err(e) => {
+                        error!("failed to get move: {}", e);
+                        let turn = Turn {
+                            state: game_state,
+                            response: None,
+                            error: Some(e.to_string()),
+                            depth: None,
+                            scores: HashMap::new(),
+                            elapsed_ms,
+                        };
+                        (turn, Err(warp::reject::custom(InternalError)))
+                    },
+                };
+
+                record(Entry::Move(turn));
+                reply
+            }
+        });
+
+    let end = warp::post()
+        .and(warp::path("end"))
+        .and(warp::body::json())
+        .map(move |state: GameState| {
+            strategy.end(&state);
+            record(Entry::End { state });
+            "".to_owned()
         });
 
-    let api = healthz.or(start).or(do_move).with(cors).with(logging);
+    let api = healthz
+        .or(start)
+        .or(do_move)
+        .or(end)
+        .with(cors)
+        .with(logging);
 
     warp::serve(api).run(([0, 0, 0, 0], 6502)).await;
 
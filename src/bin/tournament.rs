@@ -0,0 +1,464 @@
+// plays every entrant against every other, to find out which is strongest.
+//
+// every argument is an entrant, written as `name=snake` or just `snake`. a
+// snake is the URL of a snake server, or one of our strategies to play in
+// this process: `strangle` or `mcts`, optionally followed by `:` and a file
+// of weights to play with, like `strangle:weights.json`.
+//
+// duels are played between every pair of entrants, and four-player games
+// between every group of four. results are shown as wins, draws and losses,
+// followed by Elo ratings with 95% confidence intervals. in four-player games
+// each entrant is counted as having beaten everyone who went out before them.
+//
+// run this with --release. it's set up through the environment:
+//   TOURNEY_GAMES     how many games each pair or group of entrants plays
+//   TOURNEY_FORMATS   which formats to play, out of `duel` and `quad`
+//   TOURNEY_TIMEOUT   how many milliseconds each snake has to move
+//   TOURNEY_MAX_TURNS how long a game can go on before it's called a draw
+//   TOURNEY_SEED      where the starting positions and food come from
+//   TOURNEY_PARALLEL  how many games to play at once
+
+use std::{
+    cmp::Ordering,
+    env,
+    fmt::Debug,
+    fs,
+    num::NonZeroUsize,
+    str::FromStr,
+    sync::{
+        atomic::{self, AtomicUsize},
+        Mutex,
+        PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+use battlesnake_doctor_strangle::{
+    arena::{play, ArenaOptions, Contestant, Outcome, Record},
+    strategies::{strangle::score_factors::Weights, Mcts, Strangle},
+};
+use color_eyre::{eyre::eyre, Result};
+use itertools::Itertools;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use reqwest::Url;
+
+/// how many times the games are resampled to find the confidence intervals.
+const RESAMPLES: usize = 1000;
+/// how many rounds of refinement the ratings get. they settle down long
+/// before this.
+const ITERATIONS: usize = 200;
+
+/// Reads a setting from the environment, falling back to `default`.
+fn setting<T: FromStr>(name: &str, default: T) -> Result<T>
+where
+    T::Err: Debug,
+{
+    env::var(name).ok().map_or(Ok(default), |value| {
+        value
+            .parse()
+            .map_err(|e| eyre!("{name} must be a valid value: {e:?}"))
+    })
+}
+
+/// What an entrant plays with.
+#[derive(Clone)]
+enum Snake {
+    Remote(Url),
+    Strangle(Weights),
+    Mcts(Weights),
+}
+
+struct Entrant {
+    name:  String,
+    snake: Snake,
+}
+
+impl FromStr for Entrant {
+    type Err = color_eyre::Report;
+
+    fn from_str(arg: &str) -> Result<Self> {
+        let (name, spec) = arg.split_once('=').unwrap_or((arg, arg));
+        let (kind, weights) = spec.split_once(':').unwrap_or((spec, ""));
+        let weights = || -> Result<Weights> {
+            if weights.is_empty() {
+                Ok(Weights::DEFAULT)
+            } else {
+                Ok(serde_json::from_str(&fs::read_to_string(weights)?)?)
+            }
+        };
+
+        let snake = match kind {
+            "strangle" => Snake::Strangle(weights()?),
+            "mcts" => Snake::Mcts(weights()?),
+            _ => Snake::Remote(spec.parse()?),
+        };
+        Ok(Self {
+            name: name.to_owned(),
+            snake,
+        })
+    }
+}
+
+impl Entrant {
+    /// A fresh contestant for this entrant, with nothing left over from any
+    /// other game. Strategies in this process don't need to leave time for
+    /// the network, and shouldn't steal time from their opponents by
+    /// pondering.
+    fn contestant(&self) -> Contestant {
+        match &self.snake {
+            Snake::Remote(url) => Contestant::remote(&self.name, url.clone()),
+            Snake::Strangle(weights) => Contestant::local(
+                &self.name,
+                Strangle::new(Duration::ZERO)
+                    .without_pondering()
+                    .with_weights(*weights),
+            ),
+            Snake::Mcts(weights) => Contestant::local(
+                &self.name,
+                Mcts::new(Duration::ZERO).with_weights(*weights),
+            ),
+        }
+    }
+}
+
+/// A way of playing, and how many entrants each game has.
+#[derive(Clone, Copy, Debug)]
+enum Format {
+    Duel,
+    Quad,
+}
+
+impl FromStr for Format {
+    type Err = color_eyre::Report;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "duel" => Ok(Self::Duel),
+            "quad" => Ok(Self::Quad),
+            _ => Err(eyre!("unknown format {name}")),
+        }
+    }
+}
+
+impl Format {
+    const fn players(self) -> usize {
+        match self {
+            Self::Duel => 2,
+            Self::Quad => 4,
+        }
+    }
+}
+
+/// One game to play: who's in which seat, and the seed to play it with.
+struct Fixture {
+    seats: Vec<usize>,
+    seed:  u64,
+}
+
+/// Every game in the format, with each group of entrants playing `games`
+/// games. Each group plays the same seed in every seating in turn, so nobody
+/// gets a better start than anyone else.
+fn fixtures(
+    format: Format,
+    entrants: usize,
+    games: u64,
+    seed: u64,
+) -> Vec<Fixture> {
+    let players = format.players();
+    let mut fixtures = vec![];
+    for group in (0..entrants).combinations(players) {
+        for game in 0..games {
+            let seating = usize::try_from(game).unwrap_or(0) % players;
+            let mut seats = group.clone();
+            seats.rotate_left(seating);
+            fixtures.push(Fixture {
+                seats,
+                seed: seed.wrapping_add(game / players as u64),
+            });
+        }
+    }
+    fixtures
+}
+
+/// Plays all the fixtures, `parallel` at a time. Each outcome is returned
+/// along with the entrants in the order they sat.
+fn run(
+    entrants: &[Entrant],
+    fixtures: &[Fixture],
+    options: &ArenaOptions,
+    parallel: usize,
+) -> Result<Vec<(Vec<usize>, Outcome)>> {
+    let next = AtomicUsize::new(0);
+    let played = Mutex::new(vec![]);
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..parallel.clamp(1, fixtures.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| -> Result<()> {
+                    while let Some(fixture) = fixtures
+                        .get(next.fetch_add(1, atomic::Ordering::Relaxed))
+                    {
+                        let contestants: Vec<Contestant> = fixture
+                            .seats
+                            .iter()
+                            .map(|&entrant| entrants[entrant].contestant())
+                            .collect();
+                        let outcome = play(
+                            &contestants,
+                            &ArenaOptions {
+                                seed: fixture.seed,
+                                ..options.clone()
+                            },
+                        )?;
+
+                        let mut played = played
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner);
+                        played.push((fixture.seats.clone(), outcome));
+                        println!("played {}/{}", played.len(), fixtures.len());
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .map_err(|_| eyre!("a game panicked"))
+                    .and_then(|result| result)
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    Ok(played.into_inner().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Every pair of entrants in a game, and how the first did against the
+/// second.
+fn pairings(
+    seats: &[usize],
+    outcome: &Outcome,
+) -> Vec<(usize, usize, Ordering)> {
+    (0..seats.len())
+        .tuple_combinations()
+        .map(|(a, b)| (seats[a], seats[b], outcome.versus(a, b)))
+        .collect()
+}
+
+/// Elo ratings from a set of games, averaging zero.
+///
+/// The ratings are the ones that best explain the results, where a draw
+/// counts as half a win for each side. Everyone starts with a draw against
+/// everyone else, so that the ratings of entrants who never win or never lose
+/// stay finite.
+#[allow(clippy::cast_precision_loss)]
+fn ratings(entrants: usize, games: &[&[(usize, usize, Ordering)]]) -> Vec<f64> {
+    let mut scores = vec![0.0; entrants];
+    let mut meetings = vec![vec![0.0; entrants]; entrants];
+    for (a, b) in (0..entrants).tuple_combinations() {
+        scores[a] += 0.5;
+        scores[b] += 0.5;
+        meetings[a][b] += 1.0;
+        meetings[b][a] += 1.0;
+    }
+    for &(a, b, result) in games.iter().copied().flatten() {
+        let score = match result {
+            Ordering::Greater => 1.0,
+            Ordering::Equal => 0.5,
+            Ordering::Less => 0.0,
+        };
+        scores[a] += score;
+        scores[b] += 1.0 - score;
+        meetings[a][b] += 1.0;
+        meetings[b][a] += 1.0;
+    }
+
+    // the usual fixed-point iteration for the Bradley-Terry model.
+    let mut strengths = vec![1.0_f64; entrants];
+    for _ in 0..ITERATIONS {
+        for entrant in 0..entrants {
+            let expected: f64 = (0..entrants)
+                .map(|other| {
+                    meetings[entrant][other]
+                        / (strengths[entrant] + strengths[other])
+                })
+                .sum();
+            if expected > 0.0 {
+                strengths[entrant] = scores[entrant] / expected;
+            }
+        }
+        let mean =
+            strengths.iter().map(|s| s.ln()).sum::<f64>() / entrants as f64;
+        for strength in &mut strengths {
+            *strength /= mean.exp();
+        }
+    }
+
+    strengths.iter().map(|s| 400.0 * s.log10()).collect()
+}
+
+/// The 95% confidence interval for each entrant's rating, found by replaying
+/// the tournament with games picked at random from the ones that were played.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn intervals(
+    entrants: usize,
+    games: &[&[(usize, usize, Ordering)]],
+    rng: &mut StdRng,
+) -> Vec<(f64, f64)> {
+    let mut samples = vec![Vec::with_capacity(RESAMPLES); entrants];
+    for _ in 0..RESAMPLES {
+        let resampled: Vec<_> = (0..games.len())
+            .map(|_| games[rng.gen_range(0..games.len())])
+            .collect();
+        for (samples, rating) in
+            samples.iter_mut().zip(ratings(entrants, &resampled))
+        {
+            samples.push(rating);
+        }
+    }
+
+    samples
+        .into_iter()
+        .map(|mut samples| {
+            samples.sort_by(f64::total_cmp);
+            let at = |quantile: f64| {
+                samples
+                    [((samples.len() - 1) as f64 * quantile).round() as usize]
+            };
+            (at(0.025), at(0.975))
+        })
+        .collect()
+}
+
+/// Prints how everyone did against everyone else in a format, followed by
+/// their ratings.
+fn report(
+    format: Format,
+    entrants: &[Entrant],
+    played: &[(Vec<usize>, Outcome)],
+    rng: &mut StdRng,
+) {
+    let games: Vec<Vec<(usize, usize, Ordering)>> = played
+        .iter()
+        .map(|(seats, outcome)| pairings(seats, outcome))
+        .collect();
+
+    let mut records =
+        vec![vec![Record::default(); entrants.len()]; entrants.len()];
+    for &(a, b, result) in games.iter().flatten() {
+        records[a][b].add(result);
+        records[b][a].add(result.reverse());
+    }
+
+    // in a duel the total is just how many games were won. in bigger games
+    // it's who won the whole thing, not who they beat along the way.
+    let mut totals = vec![Record::default(); entrants.len()];
+    for (seats, outcome) in played {
+        for (seat, &entrant) in seats.iter().enumerate() {
+            totals[entrant].add(
+                if outcome.winners.contains(&seat) {
+                    Ordering::Greater
+                } else if outcome.winners.is_empty()
+                    && (0..seats.len())
+                        .all(|other| outcome.versus(seat, other).is_ge())
+                {
+                    Ordering::Equal
+                } else {
+                    Ordering::Less
+                },
+            );
+        }
+    }
+
+    let width = entrants
+        .iter()
+        .map(|entrant| entrant.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(10);
+
+    println!("\n{format:?}, {} games", played.len());
+    print!("{:width$}", "");
+    for entrant in entrants {
+        print!("  {:>width$}", entrant.name);
+    }
+    println!("  {:>width$}", "total");
+    for ((entrant, records), total) in
+        entrants.iter().zip(&records).zip(&totals)
+    {
+        print!("{:width$}", entrant.name);
+        for record in records {
+            if record.games() == 0 {
+                print!("  {:>width$}", "-");
+            } else {
+                print!("  {:>width$}", record.to_string());
+            }
+        }
+        println!("  {:>width$}", total.to_string());
+    }
+
+    let games: Vec<&[(usize, usize, Ordering)]> =
+        games.iter().map(Vec::as_slice).collect();
+    let ratings = ratings(entrants.len(), &games);
+    let intervals = intervals(entrants.len(), &games, rng);
+    println!("\nelo (95% confidence)");
+    for ((entrant, rating), (low, high)) in
+        entrants.iter().zip(ratings).zip(intervals)
+    {
+        println!(
+            "{:width$}  {rating:+6.0} ({low:+.0} to {high:+.0})",
+            entrant.name
+        );
+    }
+}
+
+fn main() -> Result<()> {
+    pretty_env_logger::init();
+    color_eyre::install()?;
+
+    let entrants: Vec<Entrant> = env::args()
+        .skip(1)
+        .map(|arg| arg.parse())
+        .collect::<Result<_>>()?;
+    if entrants.len() < 2 {
+        return Err(eyre!("a tournament needs at least two entrants"));
+    }
+
+    let games: u64 = setting("TOURNEY_GAMES", 100)?;
+    let formats: Vec<Format> =
+        setting("TOURNEY_FORMATS", "duel,quad".to_owned())?
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<_>>()?;
+    let seed: u64 = setting("TOURNEY_SEED", 0)?;
+    let cores = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    // every snake in a game thinks at the same time.
+    let parallel: usize = setting("TOURNEY_PARALLEL", (cores / 2).max(1))?;
+    let options = ArenaOptions {
+        timeout: setting("TOURNEY_TIMEOUT", 100)?,
+        max_turns: Some(setting("TOURNEY_MAX_TURNS", 1000)?),
+        ..ArenaOptions::default()
+    };
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    for format in formats {
+        if entrants.len() < format.players() {
+            println!("\nskipping {format:?}, there aren't enough entrants");
+            continue;
+        }
+
+        let fixtures = fixtures(format, entrants.len(), games, seed);
+        let played = run(&entrants, &fixtures, &options, parallel)?;
+        report(format, &entrants, &played, &mut rng);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,337 @@
+// tunes the evaluation weights by having the snake play itself. each round
+// plays two slightly different versions of the current weights against each
+// other, and moves the weights towards whichever version did better (SPSA).
+// the result is played against the default weights, and whichever set comes
+// out on top is written out for the snake to load with WEIGHTS_FILE.
+//
+// run this with --release. it's set up through the environment:
+//   TUNE_ROUNDS  how many rounds of tuning to run
+//   TUNE_GAMES   how many pairs of games each round plays
+//   TUNE_CHECK   how many pairs of games the final check against the defaults
+//                plays
+//   TUNE_DEPTH   how many turns ahead every snake searches
+//   TUNE_SEED    where the random numbers start from
+//   TUNE_OUT     where to write the weights. statistics go next to them.
+
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Debug,
+    fs,
+    num::NonZeroUsize,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+        PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use battlesnake_doctor_strangle::{
+    arena::{new_game, ArenaOptions, Record},
+    fightsnake::types::Direction,
+    strategies::strangle::{
+        brain::{deepen, Algorithm, BigbrainOptions},
+        game::{Game, Type},
+        score_factors::Weights,
+        transposition::TranspositionTable,
+        SnakeID,
+    },
+};
+use color_eyre::{eyre::eyre, Result};
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+use serde_json::json;
+
+/// games that get this long are called a draw.
+const MAX_TURNS: u64 = 500;
+/// each player's transposition table only lasts for one game, so it doesn't
+/// need to be very big.
+const TABLE_CAPACITY: usize = 1 << 12;
+
+/// how far apart the two versions of the weights start, as a fraction of each
+/// weight.
+const PERTURBATION: f64 = 0.2;
+/// how far the weights move at first for a round that one version wins
+/// outright.
+const STEP: f64 = 0.1;
+
+/// Reads a setting from the environment, falling back to `default`.
+fn setting<T: FromStr>(name: &str, default: T) -> Result<T>
+where
+    T::Err: Debug,
+{
+    env::var(name).ok().map_or(Ok(default), |value| {
+        value
+            .parse()
+            .map_err(|e| eyre!("{name} must be a valid value: {e:?}"))
+    })
+}
+
+/// The weights as numbers to tune. Working with logarithms keeps every weight
+/// positive, and means each one is nudged in proportion to its size.
+#[allow(clippy::cast_precision_loss)]
+fn to_params(weights: &Weights) -> [f64; 9] {
+    [
+        weights.available_squares,
+        weights.center_dist,
+        weights.depth,
+        weights.food,
+        weights.health,
+        weights.length,
+        weights.remaining_opponents,
+        weights.starvation,
+        weights.territory,
+    ]
+    .map(|weight| (weight.max(1) as f64).ln())
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn from_params(params: &[f64; 9]) -> Weights {
+    let weights = params.map(|param| param.exp().round() as i64);
+    Weights {
+        available_squares:   weights[0],
+        center_dist:         weights[1],
+        depth:               weights[2],
+        food:                weights[3],
+        health:              weights[4],
+        length:              weights[5],
+        remaining_opponents: weights[6],
+        starvation:          weights[7],
+        territory:           weights[8],
+    }
+}
+
+/// Searches for `snake_id`'s move the same way the real snake would, except
+/// to a fixed depth so that games don't depend on how busy the machine is.
+fn choose(
+    game: &Game,
+    snake_id: SnakeID,
+    weights: Weights,
+    depth: u64,
+    table: &TranspositionTable,
+) -> Result<Direction> {
+    let seen = game
+        .seen_by(snake_id)
+        .ok_or_else(|| eyre!("snake #{snake_id} isn't playing"))?;
+    let algorithm = match seen.game_type() {
+        Type::Duel => Algorithm::AlphaBeta,
+        _ => Algorithm::MaxN,
+    };
+
+    let (result, _) = deepen(
+        &seen,
+        &BigbrainOptions {
+            max_depth: depth,
+            time_limit: Duration::MAX,
+            algorithm,
+            threads: 1,
+            weights,
+        },
+        table,
+        Instant::now(),
+    )?;
+    Ok(result.direction.unwrap_or(Direction::Up))
+}
+
+/// Plays a whole game with each snake using the weights in its place in
+/// `players`. Returns the winner, or nothing for a draw.
+fn play(players: &[Weights], depth: u64, seed: u64) -> Result<Option<SnakeID>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut game = Game::try_from(new_game(
+        players.len(),
+        &ArenaOptions::default(),
+        &mut rng,
+    )?)?;
+    let tables: Vec<TranspositionTable> = players
+        .iter()
+        .map(|_| TranspositionTable::new(TABLE_CAPACITY))
+        .collect();
+
+    for _ in 0..MAX_TURNS {
+        if game.snakes.len() <= 1 {
+            break;
+        }
+
+        let moves = game
+            .snakes
+            .iter()
+            .map(|snake| {
+                let direction = choose(
+                    &game,
+                    snake.id,
+                    players[snake.id],
+                    depth,
+                    &tables[snake.id],
+                )?;
+                Ok((snake.id, direction))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        game = game.step_standard(&moves, &mut rng)?.0;
+    }
+
+    Ok(match game.snakes.as_slice() {
+        [winner] => Some(winner.id),
+        _ => None,
+    })
+}
+
+/// Plays `pairs` pairs of games between two sets of weights, swapping seats
+/// for the second game of each pair so neither side is favoured by where it
+/// starts. The games are shared out between every core on the machine.
+fn matchup(
+    ours: Weights,
+    theirs: Weights,
+    pairs: u64,
+    depth: u64,
+    seed: u64,
+) -> Result<Record> {
+    let games: Vec<(u64, bool)> = (0..pairs)
+        .flat_map(|pair| {
+            [
+                (seed.wrapping_add(pair), false),
+                (seed.wrapping_add(pair), true),
+            ]
+        })
+        .collect();
+    let next = AtomicUsize::new(0);
+    let record = Mutex::new(Record::default());
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(games.len()))
+            .map(|_| {
+                scope.spawn(|| -> Result<()> {
+                    while let Some(&(seed, swapped)) =
+                        games.get(next.fetch_add(1, Ordering::Relaxed))
+                    {
+                        let (players, us) = if swapped {
+                            ([theirs, ours], 1)
+                        } else {
+                            ([ours, theirs], 0)
+                        };
+                        let winner = play(&players, depth, seed)?;
+
+                        let mut record = record
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner);
+                        match winner {
+                            Some(winner) if winner == us => record.wins += 1,
+                            Some(_) => record.losses += 1,
+                            None => record.draws += 1,
+                        }
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .map_err(|_| eyre!("a game panicked"))
+                    .and_then(|result| result)
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    Ok(record.into_inner().unwrap_or_else(PoisonError::into_inner))
+}
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let rounds: u32 = setting("TUNE_ROUNDS", 20)?;
+    let pairs: u64 = setting("TUNE_GAMES", 4)?;
+    let check_pairs: u64 = setting("TUNE_CHECK", 20)?;
+    let depth: u64 = setting("TUNE_DEPTH", 3)?;
+    let seed: u64 = setting("TUNE_SEED", 0)?;
+    let out: PathBuf = setting("TUNE_OUT", PathBuf::from("weights.json"))?;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut params = to_params(&Weights::DEFAULT);
+
+    // the usual SPSA gain schedules, which settle down as the rounds go on.
+    let stability = f64::from(rounds) / 10.0;
+    for round in 0..rounds {
+        let k = f64::from(round);
+        let step = STEP * (1.0 + stability).powf(0.602)
+            / (k + 1.0 + stability).powf(0.602);
+        let perturbation = PERTURBATION / (k + 1.0).powf(0.101);
+
+        let signs: [f64; 9] =
+            [(); 9].map(|()| if rng.gen_bool(0.5) { 1.0 } else { -1.0 });
+        let mut plus = params;
+        let mut minus = params;
+        for ((plus, minus), sign) in plus.iter_mut().zip(&mut minus).zip(signs)
+        {
+            *plus += perturbation * sign;
+            *minus -= perturbation * sign;
+        }
+
+        let record = matchup(
+            from_params(&plus),
+            from_params(&minus),
+            pairs,
+            depth,
+            rng.next_u64(),
+        )?;
+        // positive when the plus side did better.
+        let difference = 2.0 * record.score() - 1.0;
+        for (param, sign) in params.iter_mut().zip(signs) {
+            *param += step * difference / (2.0 * perturbation * sign);
+        }
+
+        println!(
+            "round {}/{rounds}: {record}, now {:?}",
+            round + 1,
+            from_params(&params)
+        );
+    }
+
+    let tuned = from_params(&params);
+    let record =
+        matchup(tuned, Weights::DEFAULT, check_pairs, depth, rng.next_u64())?;
+    let (best, record) = if record.score() >= 0.5 {
+        (tuned, record)
+    } else {
+        println!("the tuned weights lost to the defaults, so keeping those");
+        (Weights::DEFAULT, record.flipped())
+    };
+
+    println!(
+        "{} against the other set: {record} over {} games, scoring {:.1}% ± \
+         {:.1}%",
+        best.fingerprint(),
+        record.games(),
+        record.score() * 100.0,
+        record.error() * 100.0,
+    );
+
+    fs::write(&out, serde_json::to_string_pretty(&best)?)?;
+    fs::write(
+        out.with_extension("stats.json"),
+        serde_json::to_string_pretty(&json!({
+            "weights": best,
+            "fingerprint": best.fingerprint(),
+            "opponent": if best == tuned { "default" } else { "tuned" },
+            "games": record.games(),
+            "wins": record.wins,
+            "draws": record.draws,
+            "losses": record.losses,
+            "score": record.score(),
+            "error": record.error(),
+            "rounds": rounds,
+            "depth": depth,
+            "seed": seed,
+        }))?,
+    )?;
+    println!("wrote {}", out.display());
+
+    Ok(())
+}
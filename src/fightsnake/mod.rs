@@ -1,5 +1,6 @@
 pub mod constants;
 pub mod models;
+pub mod replay;
 pub mod types;
 pub mod utils;
 
@@ -45,21 +45,32 @@ pub struct Status {
     pub version:    String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct Settings {
     pub food_spawn_chance:      Option<u64>,
     pub minimum_food:           Option<u64>,
     pub hazard_damage_per_turn: Option<u64>,
+    pub squad:                  Option<SquadSettings>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SquadSettings {
+    pub allow_body_collisions: Option<bool>,
+    pub shared_elimination:    Option<bool>,
+    pub shared_health:         Option<bool>,
+    pub shared_length:         Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Ruleset {
     pub name:     String,
     pub version:  String,
     pub settings: Option<Settings>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Game {
     pub id:      String,
     pub ruleset: Ruleset,
@@ -68,7 +79,7 @@ pub struct Game {
     pub timeout: u64,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Board {
     pub height:  i64,
     pub width:   i64,
@@ -77,14 +88,14 @@ pub struct Board {
     pub snakes:  Vec<Snake>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Customizations {
     pub color: String,
     pub head:  String,
     pub tail:  String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Snake {
     pub id:             String,
     pub name:           String,
@@ -99,7 +110,7 @@ pub struct Snake {
     pub customizations: Option<Customizations>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GameState {
     pub game:  Game,
     pub turn:  u64,
@@ -107,7 +118,7 @@ pub struct GameState {
     pub you:   Snake,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Movement {
     #[serde(rename = "move")]
     pub movement: Direction,
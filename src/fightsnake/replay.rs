@@ -0,0 +1,243 @@
+//! Recordings of the games we play, one file per game.
+//!
+//! A replay is a JSON-lines file named after the game's id. Each line is one
+//! request we were sent, in the order they arrived, and says which endpoint it
+//! was for in its `request` field:
+//!
+//! ```json
+//! {"request":"start","state":{...}}
+//! {"request":"move","state":{...},"response":{"move":"up"},"depth":7,...}
+//! {"request":"end","state":{...}}
+//! ```
+//!
+//! `state` is the game state exactly as it was sent to us. Moves also have
+//! the `response` we sent back, or the `error` we failed with instead. If the
+//! strategy said how it decided, there's also the `depth` it searched to and
+//! the `scores` it gave each snake, by their id. `elapsed_ms` is how long it
+//! took us to answer.
+//!
+//! The snake server records every game it plays into `replays`, or into
+//! `REPLAY_DIR` if that's set. Setting `RECORD_REPLAYS=false` turns recording
+//! off.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::fightsnake::models::{GameState, Movement};
+
+/// One line of a replay.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "request", rename_all = "lowercase")]
+pub enum Entry {
+    Start { state: GameState },
+    Move(Turn),
+    End { state: GameState },
+}
+
+impl Entry {
+    #[must_use]
+    pub const fn state(&self) -> &GameState {
+        match self {
+            Self::Start { state } | Self::End { state } => state,
+            Self::Move(turn) => &turn.state,
+        }
+    }
+}
+
+/// A move request, and how we answered it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Turn {
+    pub state:      GameState,
+    pub response:   Option<Movement>,
+    pub error:      Option<String>,
+    pub depth:      Option<u64>,
+    #[serde(default)]
+    pub scores:     HashMap<String, i64>,
+    pub elapsed_ms: u64,
+}
+
+/// Appends everything that happens in our games to their replay files.
+pub struct Recorder {
+    directory: PathBuf,
+}
+
+impl Recorder {
+    /// where games are recorded if nobody says otherwise.
+    pub const DEFAULT_DIRECTORY: &'static str = "replays";
+
+    /// Records games into `directory`, creating it if it isn't there yet.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the directory can't be created.
+    pub fn new(directory: impl Into<PathBuf>) -> Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        Ok(Self { directory })
+    }
+
+    /// Where the replay for `game_id` goes. Anything in the id that doesn't
+    /// belong in a file name is left out.
+    #[must_use]
+    pub fn path(&self, game_id: &str) -> PathBuf {
+        let name: String = game_id
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .collect();
+        self.directory.join(format!("{name}.jsonl"))
+    }
+
+    /// Adds `entry` to the end of its game's replay.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the replay can't be written to.
+    pub fn record(&self, entry: &Entry) -> Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(&entry.state().game.id))?
+            .write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// A whole game, read back from its replay.
+#[derive(Debug)]
+pub struct Replay {
+    pub entries: Vec<Entry>,
+}
+
+impl Replay {
+    /// # Errors
+    ///
+    /// Fails if the file can't be read, or if any line of it isn't an entry.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let entries = BufReader::new(File::open(path)?)
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| {
+                line.as_ref().map_or(true, |line| !line.trim().is_empty())
+            })
+            .map(|(number, line)| {
+                serde_json::from_str(&line?).map_err(|e| {
+                    eyre!(
+                        "line {} of {} is broken: {e}",
+                        number + 1,
+                        path.display()
+                    )
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { entries })
+    }
+
+    /// Every move we were asked for, in order.
+    pub fn turns(&self) -> impl Iterator<Item = &Turn> {
+        self.entries.iter().filter_map(|entry| match entry {
+            Entry::Move(turn) => Some(turn),
+            _ => None,
+        })
+    }
+
+    /// The move request for `turn`. If it was asked for more than once, the
+    /// last time counts.
+    #[must_use]
+    pub fn turn(&self, turn: u64) -> Option<&Turn> {
+        self.turns().filter(|t| t.state.turn == turn).last()
+    }
+
+    /// The game as it was on `turn`. That's the game we were asked to move
+    /// in, or the final state if the game was over by then.
+    #[must_use]
+    pub fn state(&self, turn: u64) -> Option<&GameState> {
+        self.turn(turn).map(|t| &t.state).or_else(|| {
+            self.entries
+                .iter()
+                .rev()
+                .map(Entry::state)
+                .find(|state| state.turn == turn)
+        })
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use std::{collections::HashMap, env, fs};
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{Entry, Recorder, Replay, Turn};
+    use crate::{
+        arena::{new_game, ArenaOptions},
+        fightsnake::{models::Movement, types::Direction},
+    };
+
+    #[test]
+    fn replays_give_back_every_turn() {
+        let directory = env::temp_dir().join("doctor-strangle-replays");
+        let recorder = Recorder::new(&directory).expect("no directory");
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut state =
+            new_game(2, &ArenaOptions::default(), &mut rng).expect("no game");
+        state.game.id = "replays/give back every turn".to_owned();
+        let path = recorder.path(&state.game.id);
+        assert_eq!(path.parent(), Some(directory.as_path()));
+        let _ = fs::remove_file(&path);
+
+        recorder
+            .record(&Entry::Start {
+                state: state.clone(),
+            })
+            .expect("couldn't record");
+        for turn in 0..3 {
+            state.turn = turn;
+            state.you.health -= 1;
+            recorder
+                .record(&Entry::Move(Turn {
+                    state:      state.clone(),
+                    response:   Some(Movement {
+                        movement: Direction::Up,
+                        shout:    None,
+                    }),
+                    error:      None,
+                    depth:      Some(turn + 1),
+                    scores:     HashMap::from([("0".to_owned(), 5)]),
+                    elapsed_ms: 10,
+                }))
+                .expect("couldn't record");
+        }
+        state.turn = 3;
+        recorder
+            .record(&Entry::End {
+                state: state.clone(),
+            })
+            .expect("couldn't record");
+
+        let replay = Replay::load(&path).expect("couldn't load");
+        assert_eq!(replay.entries.len(), 5);
+        assert_eq!(replay.turns().count(), 3);
+
+        let turn = replay.turn(1).expect("turn 1 is missing");
+        assert_eq!(turn.depth, Some(2));
+        assert_eq!(turn.state.you.health, state.you.health + 1);
+        assert_eq!(turn.scores.get("0"), Some(&5));
+
+        assert_eq!(replay.state(3).map(|s| s.turn), Some(3));
+        assert!(replay.state(4).is_none());
+
+        fs::remove_file(&path).expect("couldn't clean up");
+    }
+}
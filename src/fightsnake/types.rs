@@ -12,7 +12,7 @@ pub enum APIVersion {
     One,
 }
 
-#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum Direction {
     Left,
@@ -81,7 +81,7 @@ impl fmt::Display for Direction {
     }
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub struct Coord {
     pub x: i64,
     pub y: i64,
@@ -4,3 +4,18 @@ use crate::fightsnake::types::Coord;
 pub const fn manhattan_distance(a: Coord, b: Coord) -> i64 {
     (a.x - b.x).abs() + (a.y - b.y).abs()
 }
+
+/// Manhattan distance on a board where each edge connects to the opposite one.
+#[must_use]
+pub const fn wrapped_manhattan_distance(
+    a: Coord,
+    b: Coord,
+    width: i64,
+    height: i64,
+) -> i64 {
+    let dx = (a.x - b.x).abs();
+    let dy = (a.y - b.y).abs();
+    let dx = if width - dx < dx { width - dx } else { dx };
+    let dy = if height - dy < dy { height - dy } else { dy };
+    dx + dy
+}
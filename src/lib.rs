@@ -8,5 +8,6 @@
 #![allow(
     clippy::implicit_hasher,    // fixing this one is beyond me
 )]
+pub mod arena;
 pub mod fightsnake;
 pub mod strategies;
@@ -0,0 +1,451 @@
+use std::{
+    collections::HashMap,
+    f64::consts::SQRT_2,
+    time::{Duration, Instant},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use rand::{seq::SliceRandom, Rng};
+
+use super::{
+    strangle::{
+        game::{Game, Undo},
+        score_factors::{Scores, Weights},
+        SnakeID,
+        Strangle,
+        ME,
+    },
+    time_manager::TimeManager,
+    Strategy,
+};
+use crate::fightsnake::{models::GameState, types::Direction};
+
+/// Monte Carlo tree search, with every snake choosing its moves independently
+/// at each node (decoupled UCT), so turns are simultaneous like in the real
+/// game.
+///
+/// Unlike the minimax brain in [`super::Strangle`] this doesn't have to look
+/// at every combination of moves, so it copes much better with 3 or 4 snakes.
+pub struct Mcts {
+    time_manager: TimeManager,
+    weights:      Weights,
+}
+
+impl Mcts {
+    #[must_use]
+    pub fn new(network_margin: Duration) -> Self {
+        Self {
+            time_manager: TimeManager::new(network_margin),
+            weights:      Weights::DEFAULT,
+        }
+    }
+
+    /// Judges positions by `weights` instead of the defaults.
+    #[must_use]
+    pub const fn with_weights(mut self, weights: Weights) -> Self {
+        self.weights = weights;
+        self
+    }
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Self::new(Strangle::DEFAULT_NETWORK_MARGIN)
+    }
+}
+
+/// how much to favour rarely tried moves over ones that have done well.
+const EXPLORATION: f64 = SQRT_2;
+/// how many turns a random playout runs for before it's scored.
+const PLAYOUT_DEPTH: u64 = 8;
+/// how many squares of room a playout has to gain or lose, or the equivalent
+/// in other things, to count as very good or very bad.
+const REWARD_SQUARES: i64 = 10;
+
+type Rewards = HashMap<SnakeID, f64>;
+
+/// One of a snake's moves at a node, and how it has done so far.
+struct Arm {
+    direction: Direction,
+    visits:    u32,
+    reward:    f64,
+}
+
+struct Node {
+    game:     Game,
+    /// how many turns this node is from the root.
+    depth:    u64,
+    visits:   u32,
+    /// every living snake's moves, in the same order as `game.snakes`.
+    arms:     Vec<Vec<Arm>>,
+    /// the nodes each combination of moves leads to, as indices into the
+    /// tree.
+    children: HashMap<Vec<Direction>, usize>,
+    /// the final rewards, if the game can't go on from here.
+    terminal: Option<Rewards>,
+}
+
+impl Node {
+    fn new(game: Game, depth: u64, terminal: Option<Rewards>) -> Self {
+        let arms = game
+            .snakes
+            .iter()
+            .map(|snake| {
+                directions(&game, snake.id)
+                    .into_iter()
+                    .map(|direction| Arm {
+                        direction,
+                        visits: 0,
+                        reward: 0.0,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            game,
+            depth,
+            visits: 0,
+            arms,
+            children: HashMap::new(),
+            terminal,
+        }
+    }
+
+    /// Picks a move for every snake with UCB1, each looking only at its own
+    /// arms. Returns the index of the chosen arm for each snake.
+    fn select(&self) -> Vec<usize> {
+        let log_visits = f64::from(self.visits.max(1)).ln();
+        self.arms
+            .iter()
+            .map(|arms| {
+                arms.iter()
+                    .enumerate()
+                    .max_by(|(_, a), (_, b)| {
+                        a.ucb(log_visits).total_cmp(&b.ucb(log_visits))
+                    })
+                    .map_or(0, |(index, _)| index)
+            })
+            .collect()
+    }
+}
+
+impl Arm {
+    fn ucb(&self, log_visits: f64) -> f64 {
+        if self.visits == 0 {
+            return f64::INFINITY;
+        }
+        let visits = f64::from(self.visits);
+        EXPLORATION.mul_add((log_visits / visits).sqrt(), self.reward / visits)
+    }
+}
+
+/// The moves a snake can make. A snake with nowhere to go still has to go
+/// somewhere.
+fn directions(game: &Game, snake_id: SnakeID) -> Vec<Direction> {
+    let directions = game
+        .snakes
+        .iter()
+        .find(|snake| snake.id == snake_id)
+        .map(|snake| snake.possible_directions(&game.board))
+        .unwrap_or_default();
+
+    if directions.is_empty() {
+        vec![Direction::Up]
+    } else {
+        directions
+    }
+}
+
+fn is_terminal(game: &Game) -> bool {
+    !game.snakes.iter().any(|snake| snake.id == ME)
+        || game.multisnake && game.is_over()
+}
+
+/// Turns heuristic scores into rewards, by how much better or worse than the
+/// position at the root they are.
+struct Judge {
+    weights:  Weights,
+    /// each snake's score at the root.
+    baseline: HashMap<SnakeID, i64>,
+    /// how far a score has to move from the baseline to be clearly good or
+    /// bad.
+    scale:    f64,
+}
+
+impl Judge {
+    #[allow(clippy::cast_precision_loss)]
+    fn new(root: &Game, weights: Weights) -> Result<Self> {
+        let baseline = root
+            .scores(&Undo::default())?
+            .iter()
+            .map(|score| (score.snake_id, score.calculate(0, &weights)))
+            .collect();
+        let scale = (weights.available_squares + weights.territory).max(1)
+            * REWARD_SQUARES;
+        Ok(Self {
+            weights,
+            baseline,
+            scale: scale as f64,
+        })
+    }
+
+    /// Squashes each snake's heuristic score into a reward between 0 and 1,
+    /// with the root's score right in the middle. Deaths and wins are far
+    /// enough out that they end up at the extremes. Snakes that aren't scored
+    /// at all died earlier on, and get nothing.
+    #[allow(clippy::cast_precision_loss)]
+    fn rewards(&self, scores: &Scores, depth: u64) -> Rewards {
+        scores
+            .iter()
+            .map(|score| {
+                let gain = score.calculate(depth, &self.weights)
+                    - self.baseline.get(&score.snake_id).copied().unwrap_or(0);
+                let value = gain as f64 / self.scale;
+                (score.snake_id, 1.0 / (1.0 + (-value).exp()))
+            })
+            .collect()
+    }
+}
+
+/// Plays one turn, and works out the rewards if the game ends there.
+fn step(
+    game: &Game,
+    moves: &HashMap<SnakeID, Direction>,
+    depth: u64,
+    judge: &Judge,
+) -> Result<(Game, Option<Rewards>)> {
+    let (game, undo) = game.step(moves)?;
+    let terminal = if is_terminal(&game) {
+        Some(judge.rewards(&game.scores(&undo)?, depth))
+    } else {
+        None
+    };
+    Ok((game, terminal))
+}
+
+/// Plays random moves from `game` until the game ends or the playout gets too
+/// long, then scores the result.
+fn playout<R: Rng>(
+    game: &Game,
+    depth: u64,
+    judge: &Judge,
+    rng: &mut R,
+) -> Result<Rewards> {
+    let mut game = game.clone();
+    let mut undo = Undo::default();
+    let mut turn = depth;
+
+    loop {
+        let moves = game
+            .snakes
+            .iter()
+            .map(|snake| {
+                let direction = *directions(&game, snake.id)
+                    .choose(rng)
+                    .unwrap_or(&Direction::Up);
+                (snake.id, direction)
+            })
+            .collect();
+
+        // the playout is thrown away at the end, so there's no need to take
+        // any of its moves back.
+        game.make(&moves, &mut undo)?;
+        turn += 1;
+
+        if is_terminal(&game) || turn >= depth + PLAYOUT_DEPTH {
+            return Ok(judge.rewards(&game.scores(&undo)?, turn));
+        }
+    }
+}
+
+struct Tree {
+    nodes: Vec<Node>,
+    judge: Judge,
+}
+
+impl Tree {
+    const ROOT: usize = 0;
+
+    fn new(game: Game, weights: Weights) -> Result<Self> {
+        Ok(Self {
+            judge: Judge::new(&game, weights)?,
+            nodes: vec![Node::new(game, 0, None)],
+        })
+    }
+
+    /// Walks down the tree picking moves for everyone, adds a node where the
+    /// walk falls off the bottom, and feeds the result of a playout from there
+    /// back up the path.
+    fn iterate<R: Rng>(&mut self, rng: &mut R) -> Result<()> {
+        let mut path = Vec::new();
+        let mut index = Self::ROOT;
+
+        let rewards = loop {
+            let node = &self.nodes[index];
+            if let Some(rewards) = &node.terminal {
+                break rewards.clone();
+            }
+
+            let choice = node.select();
+            let key: Vec<Direction> = node
+                .arms
+                .iter()
+                .zip(&choice)
+                .map(|(arms, &arm)| arms[arm].direction)
+                .collect();
+            path.push((index, choice));
+
+            if let Some(&child) = node.children.get(&key) {
+                index = child;
+                continue;
+            }
+
+            let moves = node
+                .game
+                .snakes
+                .iter()
+                .map(|snake| snake.id)
+                .zip(key.iter().copied())
+                .collect();
+            let depth = node.depth + 1;
+            let (game, terminal) =
+                step(&node.game, &moves, depth, &self.judge)?;
+            let rewards = match &terminal {
+                Some(rewards) => rewards.clone(),
+                None => playout(&game, depth, &self.judge, rng)?,
+            };
+
+            let child = self.nodes.len();
+            self.nodes[index].children.insert(key, child);
+            self.nodes.push(Node::new(game, depth, terminal));
+            break rewards;
+        };
+
+        for (index, choice) in path {
+            let node = &mut self.nodes[index];
+            node.visits += 1;
+            for ((snake, arms), arm) in
+                node.game.snakes.iter().zip(&mut node.arms).zip(choice)
+            {
+                let arm = &mut arms[arm];
+                arm.visits += 1;
+                arm.reward += rewards.get(&snake.id).copied().unwrap_or(0.0);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Our most visited move at the root.
+    fn best_direction(&self) -> Option<Direction> {
+        let root = &self.nodes[Self::ROOT];
+        let (position, _) = root
+            .game
+            .snakes
+            .iter()
+            .enumerate()
+            .find(|(_, snake)| snake.id == ME)?;
+        root.arms[position]
+            .iter()
+            .max_by_key(|arm| arm.visits)
+            .map(|arm| arm.direction)
+    }
+}
+
+impl Strategy for Mcts {
+    fn get_movement(&self, game_state: GameState) -> Result<Direction> {
+        let start = Instant::now();
+
+        let time_limit = self.time_manager.budget(&game_state);
+        let game_id = game_state.game.id.clone();
+
+        let mut tree = Tree::new(Game::try_from(game_state)?, self.weights)?;
+        let mut rng = rand::thread_rng();
+
+        let mut iterations = 0;
+        while start.elapsed() < time_limit {
+            tree.iterate(&mut rng)?;
+            iterations += 1;
+        }
+
+        println!(
+            "mcts ran {iterations} iterations ({} nodes) in {:?} of \
+             {time_limit:?}",
+            tree.nodes.len(),
+            start.elapsed()
+        );
+
+        self.time_manager.finish(&game_id, start.elapsed());
+
+        tree.best_direction()
+            .ok_or_else(|| eyre!("mcts must have a move for us at the root"))
+    }
+
+    fn end(&self, game_state: &GameState) {
+        self.time_manager.end(&game_state.game.id);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::Tree;
+    use crate::{
+        arena::{new_game, ArenaOptions},
+        fightsnake::types::{Coord, Direction},
+        strategies::strangle::{game::Game, score_factors::Weights},
+    };
+
+    /// A game on an empty 11x11 board with a snake laid along each of
+    /// `bodies`, head first. We're the first one.
+    fn game(bodies: &[&[(i64, i64)]]) -> Game {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut state =
+            new_game(bodies.len(), &ArenaOptions::default(), &mut rng)
+                .expect("no game");
+        state.board.food.clear();
+        for (snake, body) in state.board.snakes.iter_mut().zip(bodies) {
+            snake.body = body.iter().map(|&(x, y)| Coord { x, y }).collect();
+            snake.head = snake.body[0];
+            snake.length = snake.body.len() as u64;
+        }
+        state.you = state.board.snakes[0].clone();
+        Game::try_from(state).expect("bad game")
+    }
+
+    /// What we'd do in `game` after a fixed number of iterations.
+    fn choose(game: Game) -> Direction {
+        let mut tree = Tree::new(game, Weights::DEFAULT).expect("no tree");
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..500 {
+            tree.iterate(&mut rng).expect("iteration failed");
+        }
+        tree.best_direction().expect("no move")
+    }
+
+    #[test]
+    fn walls_and_bodies_are_avoided() {
+        // the wall is to our left, and our own neck is below us.
+        let game =
+            game(&[&[(0, 5), (0, 4), (0, 3)], &[(8, 8), (8, 9), (8, 10)]]);
+        let direction = choose(game);
+        assert!(
+            matches!(direction, Direction::Up | Direction::Right),
+            "went {direction}"
+        );
+    }
+
+    #[test]
+    fn winning_head_to_heads_are_taken() {
+        // they're stuck against the wall with our body above them, so their
+        // only way out is to our right, where we're bigger than them.
+        let game = game(&[
+            &[(8, 5), (8, 6), (9, 6), (10, 6), (10, 7)],
+            &[(10, 5), (10, 4), (10, 3)],
+        ]);
+        assert_eq!(choose(game), Direction::Right);
+    }
+}
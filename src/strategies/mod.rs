@@ -1,13 +1,52 @@
+pub mod mcts;
 pub mod strangle;
+pub mod time_manager;
+
+use std::collections::HashMap;
 
 use color_eyre::Result;
+pub use mcts::Mcts;
 pub use strangle::Strangle;
 
 use crate::fightsnake::{models::GameState, types::Direction};
 
+/// A move, along with what the strategy thought of the game when it chose it.
+#[derive(Clone, Debug)]
+pub struct Decision {
+    pub movement: Direction,
+    /// how many turns ahead the strategy looked, if it looks ahead in turns.
+    pub depth:    Option<u64>,
+    /// how good the game looked for each snake, by their id in the game
+    /// state.
+    pub scores:   HashMap<String, i64>,
+}
+
+impl From<Direction> for Decision {
+    fn from(movement: Direction) -> Self {
+        Self {
+            movement,
+            depth: None,
+            scores: HashMap::new(),
+        }
+    }
+}
+
 pub trait Strategy {
     /// # Errors
     ///
     /// Can fail for a wide range of reasons usually due to invalid game states.
     fn get_movement(&self, game_state: GameState) -> Result<Direction>;
+
+    /// Chooses a move the same way as [`Self::get_movement`], but also says
+    /// how it came to it.
+    ///
+    /// # Errors
+    ///
+    /// Fails whenever [`Self::get_movement`] would.
+    fn decide(&self, game_state: GameState) -> Result<Decision> {
+        self.get_movement(game_state).map(Decision::from)
+    }
+
+    /// Called when a game finishes, so any per-game state can be released.
+    fn end(&self, _game_state: &GameState) {}
 }
@@ -0,0 +1,426 @@
+use std::fmt::Debug;
+
+use super::board::{Board, Topology};
+use crate::fightsnake::types::Coord;
+
+/// A set of squares, one bit per square, numbered the same way as
+/// [`Board::index`].
+///
+/// Small boards fit in a single `u128`, which is as fast as it gets. Bigger
+/// boards are spread over several words with [`Wide`], and anything bigger
+/// than that goes on the heap with [`Heap`].
+pub trait Bits: Clone + Eq + Debug {
+    /// the most squares this can hold.
+    const CAPACITY: usize;
+    const EMPTY: Self;
+
+    /// Just the square at `index`, or nothing if it doesn't fit.
+    fn bit(index: usize) -> Self;
+    fn and(&self, other: &Self) -> Self;
+    fn or(&self, other: &Self) -> Self;
+    /// These squares, minus any that are in `other`.
+    fn without(&self, other: &Self) -> Self;
+    /// Moves every square `n` places towards the higher indices, dropping any
+    /// that go past the end.
+    fn raise(&self, n: usize) -> Self;
+    /// Moves every square `n` places towards index zero, dropping any that go
+    /// past the start.
+    fn lower(&self, n: usize) -> Self;
+    fn count(&self) -> u32;
+
+    fn is_empty(&self) -> bool {
+        *self == Self::EMPTY
+    }
+}
+
+impl Bits for u128 {
+    const CAPACITY: usize = Self::BITS as usize;
+    const EMPTY: Self = 0;
+
+    fn bit(index: usize) -> Self {
+        u32::try_from(index)
+            .ok()
+            .and_then(|index| 1_u128.checked_shl(index))
+            .unwrap_or(0)
+    }
+
+    fn and(&self, other: &Self) -> Self {
+        self & other
+    }
+
+    fn or(&self, other: &Self) -> Self {
+        self | other
+    }
+
+    fn without(&self, other: &Self) -> Self {
+        self & !other
+    }
+
+    fn raise(&self, n: usize) -> Self {
+        u32::try_from(n)
+            .ok()
+            .and_then(|n| self.checked_shl(n))
+            .unwrap_or(0)
+    }
+
+    fn lower(&self, n: usize) -> Self {
+        u32::try_from(n)
+            .ok()
+            .and_then(|n| self.checked_shr(n))
+            .unwrap_or(0)
+    }
+
+    fn count(&self) -> u32 {
+        self.count_ones()
+    }
+}
+
+/// Squares spread over `WORDS` words, for boards too big for a `u128`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Wide<const WORDS: usize>([u64; WORDS]);
+
+/// Bits for boards too big for a `u128`, which is anything up to 32x32.
+pub type Large = Wide<16>;
+
+impl<const WORDS: usize> Bits for Wide<WORDS> {
+    const CAPACITY: usize = WORDS * 64;
+    const EMPTY: Self = Self([0; WORDS]);
+
+    fn bit(index: usize) -> Self {
+        let mut words = [0; WORDS];
+        if let Some(word) = words.get_mut(index / 64) {
+            *word = 1 << (index % 64);
+        }
+        Self(words)
+    }
+
+    fn and(&self, other: &Self) -> Self {
+        let mut words = self.0;
+        for (word, other) in words.iter_mut().zip(other.0) {
+            *word &= other;
+        }
+        Self(words)
+    }
+
+    fn or(&self, other: &Self) -> Self {
+        let mut words = self.0;
+        for (word, other) in words.iter_mut().zip(other.0) {
+            *word |= other;
+        }
+        Self(words)
+    }
+
+    fn without(&self, other: &Self) -> Self {
+        let mut words = self.0;
+        for (word, other) in words.iter_mut().zip(other.0) {
+            *word &= !other;
+        }
+        Self(words)
+    }
+
+    fn raise(&self, n: usize) -> Self {
+        let mut words = [0; WORDS];
+        raise_words(&self.0, &mut words, n);
+        Self(words)
+    }
+
+    fn lower(&self, n: usize) -> Self {
+        let mut words = [0; WORDS];
+        lower_words(&self.0, &mut words, n);
+        Self(words)
+    }
+
+    fn count(&self) -> u32 {
+        self.0.iter().map(|word| word.count_ones()).sum()
+    }
+}
+
+/// Squares spread over as many words as it takes, for boards too big for
+/// anything else. Every operation allocates, so this is a lot slower than the
+/// others. Missing words are empty, so sets of different lengths can still be
+/// equal.
+#[derive(Clone, Debug)]
+pub struct Heap(Vec<u64>);
+
+impl PartialEq for Heap {
+    fn eq(&self, other: &Self) -> bool {
+        let words = self.0.len().max(other.0.len());
+        (0..words).all(|index| {
+            self.0.get(index).unwrap_or(&0) == other.0.get(index).unwrap_or(&0)
+        })
+    }
+}
+
+impl Eq for Heap {}
+
+impl Bits for Heap {
+    const CAPACITY: usize = usize::MAX;
+    const EMPTY: Self = Self(Vec::new());
+
+    fn bit(index: usize) -> Self {
+        let mut words = vec![0; index / 64 + 1];
+        words[index / 64] = 1 << (index % 64);
+        Self(words)
+    }
+
+    fn and(&self, other: &Self) -> Self {
+        Self(self.0.iter().zip(&other.0).map(|(a, b)| a & b).collect())
+    }
+
+    fn or(&self, other: &Self) -> Self {
+        let (long, short) = if self.0.len() >= other.0.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let mut words = long.0.clone();
+        for (word, other) in words.iter_mut().zip(&short.0) {
+            *word |= other;
+        }
+        Self(words)
+    }
+
+    fn without(&self, other: &Self) -> Self {
+        let mut words = self.0.clone();
+        for (word, other) in words.iter_mut().zip(&other.0) {
+            *word &= !other;
+        }
+        Self(words)
+    }
+
+    fn raise(&self, n: usize) -> Self {
+        let mut words = vec![0; self.0.len() + n.div_ceil(64)];
+        raise_words(&self.0, &mut words, n);
+        Self(words)
+    }
+
+    fn lower(&self, n: usize) -> Self {
+        let mut words = vec![0; self.0.len().saturating_sub(n / 64)];
+        lower_words(&self.0, &mut words, n);
+        Self(words)
+    }
+
+    fn count(&self) -> u32 {
+        self.0.iter().map(|word| word.count_ones()).sum()
+    }
+}
+
+/// Fills `to` with the squares in `from` moved `n` places towards the higher
+/// indices. Anything that doesn't fit in `to` is dropped.
+fn raise_words(from: &[u64], to: &mut [u64], n: usize) {
+    let (skip, shift) = (n / 64, n % 64);
+    for (index, word) in to.iter_mut().enumerate().skip(skip) {
+        let source = index - skip;
+        *word = from.get(source).map_or(0, |word| word << shift);
+        if shift > 0 && source > 0 {
+            *word |=
+                from.get(source - 1).map_or(0, |word| word >> (64 - shift));
+        }
+    }
+}
+
+/// Fills `to` with the squares in `from` moved `n` places towards index zero.
+/// Anything that doesn't fit in `to` is dropped.
+fn lower_words(from: &[u64], to: &mut [u64], n: usize) {
+    let (skip, shift) = (n / 64, n % 64);
+    for (index, word) in to.iter_mut().enumerate() {
+        let source = index + skip;
+        *word = from.get(source).map_or(0, |word| word >> shift);
+        if shift > 0 {
+            *word |=
+                from.get(source + 1).map_or(0, |word| word << (64 - shift));
+        }
+    }
+}
+
+/// The squares in `coords`, leaving out any that are off the board.
+pub fn squares<B: Bits>(
+    board: &Board,
+    coords: impl IntoIterator<Item = Coord>,
+) -> B {
+    coords
+        .into_iter()
+        .filter_map(|c| board.index(c))
+        .fold(B::EMPTY, |set, index| set.or(&B::bit(index)))
+}
+
+/// The shape of a board in bits, for moving whole sets of squares around at
+/// once.
+pub struct Grid<B> {
+    width:   usize,
+    height:  usize,
+    wrapped: bool,
+    /// every square on the board.
+    pub all: B,
+    /// the left and right columns, and the bottom and top rows.
+    left:    B,
+    right:   B,
+    bottom:  B,
+    top:     B,
+}
+
+impl<B: Bits> Grid<B> {
+    pub fn new(board: &Board) -> Self {
+        let width = usize::try_from(board.width).unwrap_or(0);
+        let height = usize::try_from(board.height).unwrap_or(0);
+
+        let bottom = (0..width).fold(B::EMPTY, |row, x| row.or(&B::bit(x)));
+        let left = (0..height)
+            .fold(B::EMPTY, |column, y| column.or(&B::bit(y * width)));
+        let all = (0..height)
+            .fold(B::EMPTY, |all, y| all.or(&bottom.raise(y * width)));
+
+        Self {
+            width,
+            height,
+            wrapped: board.topology == Topology::Wrapped,
+            all,
+            right: left.raise(width.saturating_sub(1)),
+            left,
+            top: bottom.raise(width * height.saturating_sub(1)),
+            bottom,
+        }
+    }
+
+    /// Every square one move away from any of `set`'s.
+    pub fn neighbours(&self, set: &B) -> B {
+        let mut around = set
+            .without(&self.left)
+            .lower(1)
+            .or(&set.without(&self.right).raise(1))
+            .or(&set.raise(self.width).and(&self.all))
+            .or(&set.lower(self.width));
+
+        if self.wrapped {
+            let across = self.width.saturating_sub(1);
+            let up = self.width * self.height.saturating_sub(1);
+            around = around
+                .or(&set.and(&self.left).raise(across))
+                .or(&set.and(&self.right).lower(across))
+                .or(&set.and(&self.bottom).raise(up))
+                .or(&set.and(&self.top).lower(up));
+        }
+
+        around
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{squares, Bits, Grid, Heap, Wide};
+    use crate::{
+        fightsnake::types::Coord,
+        strategies::strangle::board::{Board, Topology},
+    };
+
+    const fn board(topology: Topology) -> Board {
+        Board {
+            width: 11,
+            height: 11,
+            topology,
+        }
+    }
+
+    fn around<B: Bits>(board: &Board, c: Coord) -> B {
+        Grid::new(board).neighbours(&squares(board, [c]))
+    }
+
+    #[test]
+    fn neighbours_stop_at_walls() {
+        let board = board(Topology::Bounded);
+        let corner: u128 = around(&board, Coord { x: 0, y: 0 });
+        assert_eq!(
+            corner,
+            squares(&board, [Coord { x: 1, y: 0 }, Coord { x: 0, y: 1 }])
+        );
+
+        let corner: u128 = around(&board, Coord { x: 10, y: 10 });
+        assert_eq!(
+            corner,
+            squares(&board, [Coord { x: 9, y: 10 }, Coord { x: 10, y: 9 }])
+        );
+    }
+
+    #[test]
+    fn neighbours_wrap_around_edges() {
+        let board = board(Topology::Wrapped);
+        let corner: u128 = around(&board, Coord { x: 0, y: 0 });
+        assert_eq!(
+            corner,
+            squares(
+                &board,
+                [
+                    Coord { x: 1, y: 0 },
+                    Coord { x: 0, y: 1 },
+                    Coord { x: 10, y: 0 },
+                    Coord { x: 0, y: 10 },
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn wide_boards_behave_like_small_ones() {
+        for topology in [Topology::Bounded, Topology::Wrapped] {
+            let board = board(topology);
+            for c in [
+                Coord { x: 0, y: 0 },
+                Coord { x: 5, y: 5 },
+                Coord { x: 10, y: 5 },
+                Coord { x: 3, y: 10 },
+            ] {
+                let small: u128 = around(&board, c);
+                let wide: Wide<2> = around(&board, c);
+                assert_eq!(small.count(), wide.count());
+                assert_eq!(small.to_le_bytes()[..8], wide.0[0].to_le_bytes());
+                assert_eq!(small.to_le_bytes()[8..], wide.0[1].to_le_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn wide_shifts_cross_words() {
+        let bit: Wide<3> = Wide::bit(63);
+        assert_eq!(bit.raise(1), Wide::bit(64));
+        assert_eq!(bit.raise(70), Wide::bit(133));
+        assert_eq!(Wide::<3>::bit(133).lower(70), bit);
+        assert!(bit.raise(200).is_empty());
+        assert!(bit.lower(64).is_empty());
+    }
+
+    #[test]
+    fn heap_boards_behave_like_wide_ones() {
+        for topology in [Topology::Bounded, Topology::Wrapped] {
+            let board = Board {
+                width: 40,
+                height: 40,
+                topology,
+            };
+            for c in [
+                Coord { x: 0, y: 0 },
+                Coord { x: 20, y: 20 },
+                Coord { x: 39, y: 7 },
+                Coord { x: 3, y: 39 },
+            ] {
+                let wide: Wide<25> = around(&board, c);
+                let heap: Heap = around(&board, c);
+                assert_eq!(wide.count(), heap.count());
+                for (index, word) in wide.0.iter().enumerate() {
+                    assert_eq!(heap.0.get(index).unwrap_or(&0), word);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn heap_shifts_grow_and_shrink() {
+        let bit = Heap::bit(63);
+        assert_eq!(bit.raise(1), Heap::bit(64));
+        assert_eq!(bit.raise(70), Heap::bit(133));
+        assert_eq!(Heap::bit(133).lower(70), bit);
+        assert!(bit.lower(64).is_empty());
+        assert!(
+            Heap::bit(5).or(&Heap::bit(500)).and(&Heap::bit(5)) == Heap::bit(5)
+        );
+    }
+}
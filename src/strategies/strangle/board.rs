@@ -1,9 +1,32 @@
-use crate::fightsnake::types::Coord;
+use crate::fightsnake::{
+    models::Ruleset,
+    types::{Coord, Direction},
+    utils::{manhattan_distance, wrapped_manhattan_distance},
+};
+
+/// How the edges of the board behave.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Topology {
+    /// the edges are walls.
+    Bounded,
+    /// moving off one edge re-enters on the opposite edge.
+    Wrapped,
+}
+
+impl From<&Ruleset> for Topology {
+    fn from(ruleset: &Ruleset) -> Self {
+        match ruleset.name.as_str() {
+            "wrapped" => Self::Wrapped,
+            _ => Self::Bounded,
+        }
+    }
+}
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub struct Board {
-    pub width:  i64,
-    pub height: i64,
+    pub width:    i64,
+    pub height:   i64,
+    pub topology: Topology,
 }
 
 impl Board {
@@ -13,4 +36,42 @@ impl Board {
             && coord.x < self.width
             && coord.y < self.height
     }
+
+    /// How many squares there are on the board.
+    pub fn squares(&self) -> usize {
+        usize::try_from(self.width * self.height).unwrap_or(0)
+    }
+
+    /// Where `coord` is in a list of every square on the board, row by row
+    /// from the bottom left. Squares off the board don't have one.
+    pub fn index(&self, coord: Coord) -> Option<usize> {
+        if self.contains(coord) {
+            usize::try_from(coord.x + coord.y * self.width).ok()
+        } else {
+            None
+        }
+    }
+
+    /// The square next to `coord` in the given direction. On a bounded board
+    /// this may be off the edge.
+    pub const fn neighbour(&self, coord: Coord, direction: Direction) -> Coord {
+        let neighbour = coord.neighbour(direction);
+        match self.topology {
+            Topology::Bounded => neighbour,
+            Topology::Wrapped => Coord {
+                x: neighbour.x.rem_euclid(self.width),
+                y: neighbour.y.rem_euclid(self.height),
+            },
+        }
+    }
+
+    /// The number of moves between two squares, ignoring obstacles.
+    pub const fn distance(&self, a: Coord, b: Coord) -> i64 {
+        match self.topology {
+            Topology::Bounded => manhattan_distance(a, b),
+            Topology::Wrapped => {
+                wrapped_manhattan_distance(a, b, self.width, self.height)
+            },
+        }
+    }
 }
@@ -1,54 +1,160 @@
 use std::{
-    collections::{
-        hash_map::{DefaultHasher, Entry},
-        HashMap,
-    },
-    hash::{Hash, Hasher},
+    cmp::Reverse,
+    collections::HashMap,
+    mem,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread,
     time::{Duration, Instant},
 };
 
 use color_eyre::{eyre::eyre, Result};
-#[cfg(debug_assertions)]
 use itertools::Itertools;
 
 #[cfg(debug_assertions)]
 use super::utils::Indent;
-use super::{game::Game, score_factors::ScoreFactors, SnakeID, ME};
+use super::{
+    game::{Game, Undo},
+    matrix,
+    score_factors::{ScoreFactors, Scores, Weights, MAX_SNAKES},
+    snake::Snake,
+    transposition::TranspositionTable,
+    zobrist::{self, Key},
+    SnakeID,
+    ME,
+};
 use crate::{
     fightsnake::types::Direction,
     strategies::strangle::score_factors::DeathKind,
 };
 
-type BigbrainScores = HashMap<SnakeID, ScoreFactors>;
+/// A move for each of a group of snakes.
+pub type Moves = HashMap<SnakeID, Direction>;
+/// The move each snake makes on one turn, by id. Snakes that are dead by then
+/// are left out.
+pub type Turn = [Option<Direction>; MAX_SNAKES];
+/// The moves every snake is expected to make, one turn at a time, starting
+/// from the position that was searched.
+pub type Variation = Vec<Turn>;
+/// How each of our moves turned out.
+pub type Choices = HashMap<Direction, Choice>;
+
+/// How one of our moves turned out.
+#[derive(Clone, Debug)]
+pub struct Choice {
+    /// how good the move looked to the search, by the same measure it used to
+    /// pick between moves.
+    pub score:  i64,
+    /// the scores at the end of the line of play the move led to.
+    pub scores: Scores,
+    /// how many turns that line of play lasted.
+    pub depth:  u64,
+}
+
+impl Choice {
+    const fn new(score: i64, result: &BigbrainResult) -> Self {
+        Self {
+            score,
+            scores: result.scores,
+            depth: result.depth,
+        }
+    }
+}
 
 pub struct BigbrainResult {
-    pub scores:    BigbrainScores,
+    pub scores:    Scores,
     pub direction: Option<Direction>,
     pub depth:     u64,
+    /// the line of play that `scores` came from.
+    pub variation: Variation,
+    /// every move we could have made instead of `direction`, including it, and
+    /// how each one scored. only filled in for the whole search, not for the
+    /// positions inside it.
+    ///
+    /// when everyone moves at once, a move scores the worst that can happen
+    /// after making it.
+    pub choices:   Choices,
 }
 
 impl BigbrainResult {
-    const fn inner(scores: BigbrainScores, depth: u64) -> Self {
+    /// A result with nothing in its variation yet. The variation should come
+    /// from [`SearchContext::line`], so it has room for the whole search.
+    fn inner(scores: &Scores, depth: u64, variation: Variation) -> Self {
         Self {
-            scores,
+            scores: *scores,
             direction: None,
             depth,
+            variation,
+            choices: Choices::new(),
         }
     }
 
-    const fn outer(
-        scores: BigbrainScores,
+    /// The same result, reached by `snake` moving in `direction` on the turn
+    /// at `depth`.
+    fn outer(
+        mut self,
+        snake: SnakeID,
         direction: Direction,
         depth: u64,
-    ) -> Self {
-        Self {
-            scores,
-            direction: Some(direction),
-            depth,
+    ) -> Result<Self> {
+        self.expect(snake, direction, depth)?;
+        self.direction = Some(direction);
+        Ok(self)
+    }
+
+    /// Adds `snake` moving in `direction` on the turn at `depth` to the
+    /// variation.
+    fn expect(
+        &mut self,
+        snake: SnakeID,
+        direction: Direction,
+        depth: u64,
+    ) -> Result<()> {
+        let turn = usize::try_from(depth)?;
+        if self.variation.len() <= turn {
+            self.variation.resize(turn + 1, [None; MAX_SNAKES]);
         }
+        let slot = self.variation[turn]
+            .get_mut(snake)
+            .ok_or_else(|| eyre!("only {MAX_SNAKES} snakes fit in a turn"))?;
+        *slot = Some(direction);
+        Ok(())
     }
 }
 
+/// Writes `choices` out on one line, best first.
+#[must_use]
+pub fn describe_choices(choices: &Choices) -> String {
+    choices
+        .iter()
+        .sorted_by_key(|(_, choice)| Reverse(choice.score))
+        .map(|(direction, choice)| format!("{direction} {}", choice.score))
+        .join(", ")
+}
+
+/// Writes `variation` out one turn to a line, numbering the turns from
+/// `first_turn` and calling each snake whatever `name` says.
+pub fn describe_variation(
+    variation: &[Turn],
+    first_turn: u64,
+    name: impl Fn(SnakeID) -> String,
+) -> String {
+    (first_turn..)
+        .zip(variation)
+        .map(|(turn, moves)| {
+            format!(
+                "turn {turn}: {}",
+                moves
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(snake_id, direction)| {
+                        direction.map(|d| format!("{} {d}", name(snake_id)))
+                    })
+                    .join(", ")
+            )
+        })
+        .join("\n")
+}
+
 #[cfg(debug_assertions)]
 macro_rules! trace {
     ($($tts:tt)*) => {
@@ -61,127 +167,335 @@ macro_rules! trace {
     ($($tts:tt)*) => {};
 }
 
-fn calculate_hash(game: &Game) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    game.hash(&mut hasher);
-    hasher.finish()
+/// The zobrist key for a leaf of the search. Leaf scores depend on who just
+/// died and how, so that is folded in along with the position itself.
+fn leaf_key(game: &Game, undo: &Undo) -> Key {
+    undo.deaths()
+        .map(|(snake, death_kind)| {
+            snake
+                .key()
+                .wrapping_add(zobrist::death(snake.id, death_kind))
+        })
+        .fold(game.key(), Key::wrapping_add)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// every snake picks the move that's best for its own team, in turn order.
+    MaxN,
+    /// we pick the move that's best for us, and assume everyone else picks
+    /// the move that's worst for us. this lets whole branches be skipped once
+    /// they can't change the outcome, so it's much faster for duels.
+    AlphaBeta,
+    /// every snake moves at once, like in the real game. each turn is solved
+    /// as a matrix game between our team's moves and everyone else's.
+    Simultaneous(Solver),
+}
+
+/// How the matrix game for each turn of a [`Algorithm::Simultaneous`] search
+/// is solved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Solver {
+    /// pick the move with the best worst case. this is as pessimistic as
+    /// letting the other snakes see our move first.
+    MaxMin,
+    /// pick the move we'd play most often if we mixed our moves up in the
+    /// best possible way. we only ever play that one move though, so it's
+    /// judged by its worst case just the same.
+    Mixed,
 }
 
+#[derive(Clone, Copy, Debug)]
 pub struct BigbrainOptions {
     pub max_depth:  u64,
     pub time_limit: Duration,
+    pub algorithm:  Algorithm,
+    /// how many threads to split the search between.
+    pub threads:    usize,
+    /// what the search is trying to achieve, and how much it cares about each
+    /// part of it.
+    pub weights:    Weights,
 }
 
+/// Everything a single search carries from node to node.
+pub struct SearchContext<'a> {
+    pub known_scores: &'a TranspositionTable,
+    pub start:        Instant,
+    pub options:      &'a BigbrainOptions,
+    /// how many nodes have been visited so far.
+    pub nodes:        u64,
+    /// lets another thread call the search off early.
+    pub stop:         Option<&'a AtomicBool>,
+    /// buffers for each turn of the search, indexed by depth.
+    plies:            Vec<Ply>,
+    /// variations from results nobody needs any more, kept to be reused.
+    spare_lines:      Vec<Variation>,
+}
+
+/// What one turn of the search needs to play its moves and take them back.
+#[derive(Default)]
+struct Ply {
+    undo:  Undo,
+    /// the moves for the turn after this one, as they're being decided.
+    moves: Moves,
+}
+
+impl<'a> SearchContext<'a> {
+    #[must_use]
+    pub const fn new(
+        known_scores: &'a TranspositionTable,
+        start: Instant,
+        options: &'a BigbrainOptions,
+        stop: Option<&'a AtomicBool>,
+    ) -> Self {
+        Self {
+            known_scores,
+            start,
+            options,
+            nodes: 0,
+            stop,
+            plies: Vec::new(),
+            spare_lines: Vec::new(),
+        }
+    }
+
+    fn out_of_time(&self) -> bool {
+        self.start.elapsed() >= self.options.time_limit
+            || self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    /// Borrows the buffers for the turn at `depth`. They have to be given back
+    /// with [`Self::return_ply`] for the next node at that depth to reuse.
+    fn take_ply(&mut self, depth: usize) -> Ply {
+        self.plies.get_mut(depth).map(mem::take).unwrap_or_default()
+    }
+
+    fn return_ply(&mut self, depth: usize, ply: Ply) {
+        if self.plies.len() <= depth {
+            self.plies.resize_with(depth + 1, Ply::default);
+        }
+        self.plies[depth] = ply;
+    }
+
+    /// An empty variation with room for the whole search, reusing one that
+    /// was thrown away if there is one.
+    fn line(&mut self) -> Variation {
+        self.spare_lines.pop().unwrap_or_else(|| {
+            Vec::with_capacity(
+                usize::try_from(self.options.max_depth)
+                    .unwrap_or(usize::MAX)
+                    .min(MAX_PREALLOCATED_TURNS),
+            )
+        })
+    }
+
+    /// Throws away a result, keeping its variation for the rest of the search
+    /// (or the next one through this context) to reuse.
+    pub fn discard(&mut self, result: BigbrainResult) {
+        let mut line = result.variation;
+        line.clear();
+        self.spare_lines.push(line);
+    }
+}
+
+/// the most turns a variation gets room for up front. searches never get
+/// anywhere near this deep, but one without a depth limit shouldn't try to
+/// make room for every turn it might reach.
+const MAX_PREALLOCATED_TURNS: usize = 64;
+
 fn should_exit(game: &Game, depth: u64, max_depth: u64) -> bool {
     !game.snakes.iter().any(|s| s.id == ME)
-        || game.multisnake && game.snakes.len() <= 1
+        || game.multisnake && game.is_over()
         || depth == max_depth
 }
 
-#[allow(clippy::too_many_lines)]
+/// Scores the position a turn has just left us in, looking it up in the
+/// transposition table first.
+fn leaf_scores(
+    game: &Game,
+    undo: &Undo,
+    ctx: &SearchContext,
+) -> Result<Scores> {
+    let key = leaf_key(game, undo);
+    if let Some(scores) = ctx.known_scores.get(key) {
+        return Ok(scores);
+    }
+    let scores = game.scores(undo)?;
+    ctx.known_scores.insert(key, scores);
+    Ok(scores)
+}
+
+/// Plays out a full turn of moves in place, and takes it back again once the
+/// resulting position has been dealt with.
+///
+/// If the search should stop at the new position, it's scored as a leaf and
+/// handed to `leaf`. Otherwise the search carries on from there with
+/// `search_on`, which gets an empty set of moves to fill in for the next turn.
+fn advance<T>(
+    game: &mut Game,
+    moves: &Moves,
+    depth: u64,
+    ctx: &mut SearchContext,
+    leaf: impl FnOnce(BigbrainResult) -> T,
+    search_on: impl FnOnce(
+        &mut Game,
+        &mut Moves,
+        &mut SearchContext,
+    ) -> Result<Option<T>>,
+) -> Result<Option<T>> {
+    let ply_index = usize::try_from(depth)?;
+    let mut ply = ctx.take_ply(ply_index);
+    game.make(moves, &mut ply.undo)?;
+
+    let result = if should_exit(game, depth, ctx.options.max_depth) {
+        leaf_scores(game, &ply.undo, ctx).map(|scores| {
+            let line = ctx.line();
+            Some(leaf(BigbrainResult::inner(&scores, depth, line)))
+        })
+    } else {
+        search_on(game, &mut ply.moves, ctx)
+    };
+
+    game.unmake(&mut ply.undo);
+    ply.moves.clear();
+    ctx.return_ply(ply_index, ply);
+    result
+}
+
+/// The result we assume before any moves have been tried: everybody dies.
+fn everyone_dies(
+    game: &Game,
+    depth: u64,
+    ctx: &mut SearchContext,
+) -> BigbrainResult {
+    let mut scores = Scores::new();
+    for snake in &game.snakes {
+        scores.insert(ScoreFactors::dead(
+            snake,
+            DeathKind::Normal,
+            game.multisnake,
+        ));
+    }
+    BigbrainResult::inner(&scores, depth, ctx.line())
+}
+
+/// How good `scores` are for `snake`'s whole team. Without squads this is just
+/// the snake's own score.
+fn team_score(
+    scores: &Scores,
+    snake: &Snake,
+    depth: u64,
+    weights: &Weights,
+) -> i64 {
+    scores
+        .iter()
+        .filter(|score| score.is_team_of(snake))
+        .map(|score| score.calculate(depth, weights))
+        .sum()
+}
+
+/// How much `snake` likes `scores`, and whether it's after them being high or
+/// low. In max-n every snake looks out for its own team. Alpha-beta assumes
+/// everyone else is out to get us instead, so it only ever looks at our
+/// score.
+fn judge(
+    game: &Game,
+    snake: &Snake,
+    scores: &Scores,
+    depth: u64,
+    options: &BigbrainOptions,
+) -> Result<(i64, bool)> {
+    if options.algorithm == Algorithm::AlphaBeta {
+        let me = find_me(game)?;
+        Ok((
+            team_score(scores, me, depth, &options.weights),
+            snake.is_ally(me),
+        ))
+    } else {
+        Ok((team_score(scores, snake, depth, &options.weights), true))
+    }
+}
+
+/// Max-n search. Every snake picks the move that's best for its own team, one
+/// after the other.
+///
+/// `game` is played forward and back again in place as the search goes, so it
+/// ends up exactly as it started. `moves` holds the moves picked so far this
+/// turn.
+///
 /// # Errors
 ///
 /// Can fail if something is wrong with the input data, for example if a snake
 /// has no body.
 pub fn bigbrain(
-    game: &Game,
+    game: &mut Game,
     snake_index: usize,
     depth: u64,
-    moves: &HashMap<SnakeID, Direction>,
-    known_scores: &mut HashMap<u64, HashMap<SnakeID, ScoreFactors>>,
-    start: Instant,
-    options: &BigbrainOptions,
+    moves: &mut Moves,
+    ctx: &mut SearchContext,
 ) -> Result<Option<BigbrainResult>> {
-    if start.elapsed() >= options.time_limit {
+    if ctx.out_of_time() {
         return Ok(None);
     }
+    ctx.nodes += 1;
 
     #[cfg(debug_assertions)]
     let align = Indent(depth, snake_index as u64);
 
-    let snake = &game.snakes[snake_index];
-
     trace!(
         "{align}bigbrain running for snake #{} on depth {}/{} (snakes: {:?}, \
          pending moves: {:?})",
-        snake.id,
+        game.snakes[snake_index].id,
         depth,
-        options.max_depth,
+        ctx.options.max_depth,
         game.snakes.iter().map(|snake| snake.id).join(", "),
         moves
     );
 
-    let mut game = game.clone();
-    let mut moves = moves.clone();
-
-    if snake.id == ME && depth > 0 {
+    if game.snakes[snake_index].id == ME && depth > 0 {
         trace!("{align}we've hit a new depth");
 
-        // remove moves for dead snakes
-        moves.retain(|snake_id, _| {
-            game.snakes.iter().any(|snake| snake.id == *snake_id)
-        });
-
-        let (new_game, freespace, death_kind_map) = game.step(&moves)?;
-
-        game = new_game;
-        moves.clear();
-
-        trace!("{align}game stepped and moves cleared.");
-
-        if should_exit(&game, depth, options.max_depth) {
-            let hash = calculate_hash(&game);
-            let scores = known_scores.entry(hash).or_insert({
-                // score snakes still in the game
-                let mut scores = HashMap::new();
-                for snake in &game.snakes {
-                    scores.insert(
-                        snake.id,
-                        game.score(snake, &freespace, DeathKind::Normal)?,
-                    );
-                }
+        return advance(
+            game,
+            moves,
+            depth,
+            ctx,
+            |result| {
+                trace!("{align}propagating up!");
+                result
+            },
+            |game, moves, ctx| {
+                trace!("{align}game stepped and moves cleared.");
+                bigbrain_moves(game, snake_index, depth, moves, ctx)
+            },
+        );
+    }
 
-                // add bad scores for anyone who died
-                for snake in &game.prev_snakes {
-                    if let Entry::Vacant(e) = scores.entry(snake.id) {
-                        e.insert(ScoreFactors::dead(
-                            snake.id,
-                            *death_kind_map.get(&snake.id).ok_or(eyre!(
-                                "snake died without a death_kind_map entry"
-                            ))?,
-                            game.multisnake,
-                        ));
-                    }
-                }
+    bigbrain_moves(game, snake_index, depth, moves, ctx)
+}
 
-                scores
-            });
+/// Tries each of a snake's moves for [`bigbrain`], and picks the best.
+fn bigbrain_moves(
+    game: &mut Game,
+    snake_index: usize,
+    depth: u64,
+    moves: &mut Moves,
+    ctx: &mut SearchContext,
+) -> Result<Option<BigbrainResult>> {
+    #[cfg(debug_assertions)]
+    let align = Indent(depth, snake_index as u64);
 
-            trace!("{align}propagating up!");
-            return Ok(Some(BigbrainResult::inner(scores.clone(), depth)));
-        }
-    }
+    let snake = &game.snakes[snake_index];
+    let snake_id = snake.id;
+    let dead = ScoreFactors::dead(snake, DeathKind::Normal, game.multisnake);
 
-    let directions = snake.possible_directions(&game.board);
     let mut best_direction = Direction::Up;
 
-    let mut has_best_result = false;
-    let mut best_result = BigbrainResult::inner(
-        game.snakes
-            .iter()
-            .map(|snake| {
-                (
-                    snake.id,
-                    ScoreFactors::dead(
-                        snake.id,
-                        DeathKind::Normal,
-                        game.multisnake,
-                    ),
-                )
-            })
-            .collect(),
-        depth,
-    );
+    let mut best_score = None;
+    let mut best_result = everyone_dies(game, depth, ctx);
+
+    let root = snake_id == ME && depth == 0;
+    let mut choices = Choices::new();
 
     let next_snake_index = (snake_index + 1) % game.snakes.len();
     let next_depth = if next_snake_index == ME {
@@ -190,19 +504,15 @@ pub fn bigbrain(
         depth
     };
 
-    for direction in directions {
-        trace!("{align}snake {} trying {direction}", snake.id);
+    for &direction in Direction::iter() {
+        if !game.snakes[snake_index].can_move(&game.board, direction) {
+            continue;
+        }
 
-        moves.insert(snake.id, direction);
-        let result = bigbrain(
-            &game,
-            next_snake_index,
-            next_depth,
-            &moves,
-            known_scores,
-            start,
-            options,
-        )?;
+        trace!("{align}snake {snake_id} trying {direction}");
+
+        moves.insert(snake_id, direction);
+        let result = bigbrain(game, next_snake_index, next_depth, moves, ctx)?;
 
         let mut result = if let Some(result) = result {
             result
@@ -212,9 +522,7 @@ pub fn bigbrain(
         };
 
         // ensure we always have our own score in here
-        result.scores.entry(snake.id).or_insert_with(|| {
-            ScoreFactors::dead(snake.id, DeathKind::Normal, game.multisnake)
-        });
+        result.scores.or_insert(dead);
 
         trace!(
             "{align}moves {:?} on depth {depth} gets the following scores:\n{}",
@@ -222,35 +530,37 @@ pub fn bigbrain(
             result
                 .scores
                 .iter()
-                .map(|(snake_id, score)| format!(
-                    "{snake_id}: {}\n{score}",
-                    score.calculate(result.depth)
+                .map(|score| format!(
+                    "{}: {}\n{score}",
+                    score.snake_id,
+                    score.calculate(result.depth, &ctx.options.weights)
                 ))
                 .join("\n"),
         );
 
-        if has_best_result {
-            let score = result
-                .scores
-                .get(&snake.id)
-                .unwrap_or(&ScoreFactors::dead(
-                    snake.id,
-                    DeathKind::Normal,
-                    game.multisnake,
-                ))
-                .calculate(result.depth);
+        let (score, maximising) = judge(
+            game,
+            &game.snakes[snake_index],
+            &result.scores,
+            result.depth,
+            ctx.options,
+        )?;
+        if root {
+            choices.insert(direction, Choice::new(score, &result));
+        }
 
+        if let Some(best) = best_score {
             trace!("{align}comparing {score} against previous best...");
-            if score
-                > best_result.scores[&snake.id].calculate(best_result.depth)
-            {
+            if maximising && score > best || !maximising && score < best {
                 trace!(
                     "{align}{direction} is better! setting that as best score."
                 );
-                best_result = result;
+                best_score = Some(score);
+                ctx.discard(mem::replace(&mut best_result, result));
                 best_direction = direction;
             } else {
                 trace!("{align}worse...");
+                ctx.discard(result);
             }
         } else {
             trace!(
@@ -258,36 +568,1027 @@ pub fn bigbrain(
                 result
                     .scores
                     .iter()
-                    .map(|(snake_id, score)| format!(
-                        "snake {snake_id}: {}",
-                        score.calculate(result.depth)
+                    .map(|score| format!(
+                        "snake {}: {}",
+                        score.snake_id,
+                        score.calculate(result.depth, &ctx.options.weights)
                     ))
                     .join(", ")
             );
-            best_result = result;
+            best_score = Some(score);
+            ctx.discard(mem::replace(&mut best_result, result));
             best_direction = direction;
-            has_best_result = true;
         }
     }
+    moves.remove(&snake_id);
 
     trace!(
-        "{align}snake {}'s best move at this depth is {best_direction} with a \
-         score of {}",
-        snake.id,
-        best_result
-            .scores
-            .get(&snake.id)
-            .unwrap_or(&ScoreFactors::dead(
-                snake.id,
-                DeathKind::Normal,
-                game.multisnake
-            ))
-            .calculate(best_result.depth)
+        "{align}snake {snake_id}'s best move at this depth is \
+         {best_direction} with a score of {best_score:?}"
     );
 
-    Ok(Some(BigbrainResult::outer(
-        best_result.scores,
-        best_direction,
-        best_result.depth,
-    )))
+    let mut result = best_result.outer(snake_id, best_direction, depth)?;
+    result.choices = choices;
+    Ok(Some(result))
+}
+
+/// Two-player search for duels, using alpha-beta pruning.
+///
+/// We maximise our team's score and assume the opponent minimises it. When the
+/// opponent really is just trying to beat us this picks the same move as
+/// [`bigbrain`], but it can stop looking at a branch as soon as it's clear that
+/// branch won't be chosen.
+///
+/// # Errors
+///
+/// Can fail if something is wrong with the input data, for example if a snake
+/// has no body.
+pub fn alphabeta(
+    game: &mut Game,
+    snake_index: usize,
+    depth: u64,
+    moves: &mut Moves,
+    alpha: i64,
+    beta: i64,
+    ctx: &mut SearchContext,
+) -> Result<Option<BigbrainResult>> {
+    if ctx.out_of_time() {
+        return Ok(None);
+    }
+    ctx.nodes += 1;
+
+    if game.snakes[snake_index].id == ME && depth > 0 {
+        return advance(
+            game,
+            moves,
+            depth,
+            ctx,
+            |result| result,
+            |game, moves, ctx| {
+                alphabeta_moves(
+                    game,
+                    snake_index,
+                    depth,
+                    moves,
+                    alpha,
+                    beta,
+                    ctx,
+                )
+            },
+        );
+    }
+
+    alphabeta_moves(game, snake_index, depth, moves, alpha, beta, ctx)
+}
+
+/// Tries each of a snake's moves for [`alphabeta`], and picks the best one for
+/// us or the worst one for us depending on whose side the snake is on.
+fn alphabeta_moves(
+    game: &mut Game,
+    snake_index: usize,
+    depth: u64,
+    moves: &mut Moves,
+    mut alpha: i64,
+    mut beta: i64,
+    ctx: &mut SearchContext,
+) -> Result<Option<BigbrainResult>> {
+    let snake = &game.snakes[snake_index];
+    let snake_id = snake.id;
+    let dead = ScoreFactors::dead(snake, DeathKind::Normal, game.multisnake);
+    let maximising = snake.is_ally(find_me(game)?);
+
+    let mut best_direction = Direction::Up;
+    let mut best_score = None;
+    let mut best_result = everyone_dies(game, depth, ctx);
+
+    let root = snake_id == ME && depth == 0;
+    let mut choices = Choices::new();
+
+    let next_snake_index = (snake_index + 1) % game.snakes.len();
+    let next_depth = if next_snake_index == ME {
+        depth + 1
+    } else {
+        depth
+    };
+
+    for &direction in Direction::iter() {
+        if !game.snakes[snake_index].can_move(&game.board, direction) {
+            continue;
+        }
+
+        moves.insert(snake_id, direction);
+        let Some(mut result) = alphabeta(
+            game,
+            next_snake_index,
+            next_depth,
+            moves,
+            alpha,
+            beta,
+            ctx,
+        )?
+        else {
+            return Ok(None);
+        };
+
+        // ensure we always have our own score in here
+        result.scores.or_insert(dead);
+
+        let score = team_score(
+            &result.scores,
+            find_me(game)?,
+            result.depth,
+            &ctx.options.weights,
+        );
+        if root {
+            choices.insert(direction, Choice::new(score, &result));
+        }
+
+        let better = best_score.is_none_or(|best| {
+            if maximising {
+                score > best
+            } else {
+                score < best
+            }
+        });
+        if better {
+            best_score = Some(score);
+            ctx.discard(mem::replace(&mut best_result, result));
+            best_direction = direction;
+        } else {
+            ctx.discard(result);
+        }
+
+        // every one of our first moves is searched in full, so that the
+        // choices say how good each one really is. the benchmark shows how
+        // many nodes that costs.
+        if maximising && !root {
+            alpha = alpha.max(score);
+        } else if !maximising {
+            beta = beta.min(score);
+        }
+        if alpha >= beta {
+            break;
+        }
+    }
+    moves.remove(&snake_id);
+
+    let mut result = best_result.outer(snake_id, best_direction, depth)?;
+    result.choices = choices;
+    Ok(Some(result))
+}
+
+/// Every combination of moves the given snakes can make this turn.
+fn joint_moves<'a>(
+    snakes: impl Iterator<Item = &'a Snake>,
+    game: &Game,
+) -> Vec<Moves> {
+    snakes.fold(vec![HashMap::new()], |combinations, snake| {
+        let mut directions = snake.possible_directions(&game.board);
+        if directions.is_empty() {
+            // a snake with nowhere to go still has to go somewhere.
+            directions.push(Direction::Up);
+        }
+
+        combinations
+            .iter()
+            .flat_map(|moves| {
+                directions.iter().map(|&direction| {
+                    let mut moves = moves.clone();
+                    moves.insert(snake.id, direction);
+                    moves
+                })
+            })
+            .collect()
+    })
+}
+
+/// Our snake, which the search can't go on without.
+fn find_me(game: &Game) -> Result<&Snake> {
+    game.snakes
+        .iter()
+        .find(|snake| snake.id == ME)
+        .ok_or_else(|| eyre!("we should still be alive at this point"))
+}
+
+/// Every combination of moves for our team, and every combination of moves
+/// for everyone else. These are the rows and columns of a turn's matrix game.
+fn team_moves(game: &Game, me: &Snake) -> (Vec<Moves>, Vec<Moves>) {
+    (
+        joint_moves(game.snakes.iter().filter(|s| s.is_ally(me)), game),
+        joint_moves(game.snakes.iter().filter(|s| !s.is_ally(me)), game),
+    )
+}
+
+/// Searches one cell of a turn's matrix game, where everyone has picked their
+/// move. Returns the value of the cell to our team, along with the line of
+/// play the value came from.
+fn matrix_cell(
+    game: &mut Game,
+    depth: u64,
+    solver: Solver,
+    moves: &Moves,
+    me: &Snake,
+    ctx: &mut SearchContext,
+) -> Result<Option<(i64, BigbrainResult)>> {
+    let weights = ctx.options.weights;
+    advance(
+        game,
+        moves,
+        depth + 1,
+        ctx,
+        |result| {
+            (
+                team_score(&result.scores, me, result.depth, &weights),
+                result,
+            )
+        },
+        |game, _, ctx| matrix_turn(game, depth + 1, solver, ctx),
+    )
+}
+
+/// Solves a turn's matrix game once every cell has been searched. The cells
+/// that weren't picked are thrown away into `ctx`.
+fn solve_turn(
+    ours: &[Moves],
+    theirs: &[Moves],
+    cells: Vec<Vec<(i64, BigbrainResult)>>,
+    depth: u64,
+    solver: Solver,
+    ctx: &mut SearchContext,
+) -> Result<Option<(i64, BigbrainResult)>> {
+    let (payoffs, results): (Vec<Vec<_>>, Vec<Vec<_>>) =
+        cells.into_iter().map(|row| row.into_iter().unzip()).unzip();
+
+    let solution = match solver {
+        Solver::MaxMin => matrix::max_min(&payoffs),
+        Solver::Mixed => matrix::mixed(&payoffs),
+    };
+
+    // the game is only decided within the horizon if every line of play ends
+    // early, not just the one we report.
+    let deepest = results
+        .iter()
+        .flatten()
+        .map(|r| r.depth)
+        .max()
+        .unwrap_or(depth);
+    let mut choices = Choices::new();
+    if depth == 0 {
+        for ((moves, row), cells) in ours.iter().zip(&payoffs).zip(&results) {
+            let (Some(&direction), Some((&worst, cell))) = (
+                moves.get(&ME),
+                row.iter().zip(cells).min_by_key(|(payoff, _)| **payoff),
+            ) else {
+                continue;
+            };
+            if choices
+                .get(&direction)
+                .is_none_or(|best| best.score < worst)
+            {
+                choices.insert(direction, Choice::new(worst, cell));
+            }
+        }
+    }
+
+    let mut picked = None;
+    for (row, cells) in results.into_iter().enumerate() {
+        for (column, cell) in cells.into_iter().enumerate() {
+            if (row, column) == (solution.row, solution.column) {
+                picked = Some(cell);
+            } else {
+                ctx.discard(cell);
+            }
+        }
+    }
+    let mut result =
+        picked.ok_or_else(|| eyre!("the solution must be one of the cells"))?;
+    let direction = ours[solution.row]
+        .get(&ME)
+        .copied()
+        .ok_or_else(|| eyre!("our team's moves must include our own"))?;
+    for (&snake, &direction) in
+        ours[solution.row].iter().chain(&theirs[solution.column])
+    {
+        result.expect(snake, direction, depth)?;
+    }
+    result.direction = Some(direction);
+    result.depth = deepest;
+
+    result.choices = choices;
+
+    Ok(Some((solution.value, result)))
+}
+
+/// Solves one turn of a simultaneous search. Returns the value of the turn to
+/// our team, along with the line of play the value came from.
+fn matrix_turn(
+    game: &mut Game,
+    depth: u64,
+    solver: Solver,
+    ctx: &mut SearchContext,
+) -> Result<Option<(i64, BigbrainResult)>> {
+    if ctx.out_of_time() {
+        return Ok(None);
+    }
+    ctx.nodes += 1;
+
+    // the game is about to be played forward, so we can't keep borrowing it.
+    let me = find_me(game)?.clone();
+    let (ours, theirs) = team_moves(game, &me);
+
+    let mut cells = Vec::with_capacity(ours.len());
+    for our_moves in &ours {
+        let mut row = Vec::with_capacity(theirs.len());
+        for their_moves in &theirs {
+            let mut moves = our_moves.clone();
+            moves.extend(their_moves);
+
+            let Some(cell) =
+                matrix_cell(game, depth, solver, &moves, &me, ctx)?
+            else {
+                return Ok(None);
+            };
+            row.push(cell);
+        }
+        cells.push(row);
+    }
+
+    solve_turn(&ours, &theirs, cells, depth, solver, ctx)
+}
+
+/// Search where every snake moves at the same time, instead of one after the
+/// other like in [`bigbrain`] and [`alphabeta`].
+///
+/// Each turn, every combination of our team's moves and everyone else's moves
+/// is searched, and the resulting matrix of scores is solved with `solver` to
+/// pick our move. The depth of the result is the deepest any line of play
+/// reached.
+///
+/// # Errors
+///
+/// Can fail if something is wrong with the input data, for example if a snake
+/// has no body.
+pub fn simultaneous(
+    game: &mut Game,
+    solver: Solver,
+    ctx: &mut SearchContext,
+) -> Result<Option<BigbrainResult>> {
+    Ok(matrix_turn(game, 0, solver, ctx)?.map(|(_, result)| result))
+}
+
+/// Works through `tasks` on `ctx.options.threads` threads at once, each with
+/// its own context sharing `ctx`'s transposition table. Results come back in
+/// the same order as the tasks, or not at all if the search ran out of time.
+fn in_parallel<T, R>(
+    tasks: &[T],
+    ctx: &mut SearchContext,
+    work: impl Fn(&T, &mut SearchContext) -> Result<Option<R>> + Sync,
+) -> Result<Option<Vec<R>>>
+where
+    T: Sync,
+    R: Send,
+{
+    let next = AtomicUsize::new(0);
+    let threads = ctx.options.threads.clamp(1, tasks.len().max(1));
+    let (known_scores, start, options, stop) =
+        (ctx.known_scores, ctx.start, ctx.options, ctx.stop);
+
+    let outcomes = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut ctx =
+                        SearchContext::new(known_scores, start, options, stop);
+                    let mut done = vec![];
+                    let outcome = loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(task) = tasks.get(index) else {
+                            break Ok(true);
+                        };
+                        match work(task, &mut ctx) {
+                            Ok(Some(result)) => done.push((index, result)),
+                            Ok(None) => break Ok(false),
+                            Err(e) => break Err(e),
+                        }
+                    };
+                    (
+                        ctx.nodes,
+                        outcome.map(|finished| finished.then_some(done)),
+                    )
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker.join().map_err(|_| eyre!("a search thread panicked"))
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    let mut results = Vec::with_capacity(tasks.len());
+    let mut finished = true;
+    for (nodes, outcome) in outcomes {
+        ctx.nodes += nodes;
+        match outcome? {
+            Some(done) => results.extend(done),
+            None => finished = false,
+        }
+    }
+    if !finished {
+        return Ok(None);
+    }
+
+    results.sort_unstable_by_key(|(index, _)| *index);
+    Ok(Some(
+        results.into_iter().map(|(_, result)| result).collect(),
+    ))
+}
+
+/// Picks the best of a snake's moves the same way [`bigbrain`] or
+/// [`alphabeta`] would have, so a split search agrees with an unsplit one.
+/// How every move scored goes in the choices of the result.
+fn pick(
+    game: &Game,
+    snake: &Snake,
+    choices: impl IntoIterator<Item = (Direction, BigbrainResult)>,
+    options: &BigbrainOptions,
+) -> Result<(Direction, BigbrainResult)> {
+    let mut best: Option<(i64, Direction, BigbrainResult)> = None;
+    let mut scores = Choices::new();
+
+    for (direction, mut result) in choices {
+        // ensure we always have our own score in here
+        result.scores.or_insert(ScoreFactors::dead(
+            snake,
+            DeathKind::Normal,
+            game.multisnake,
+        ));
+
+        let (score, maximising) =
+            judge(game, snake, &result.scores, result.depth, options)?;
+        scores.insert(direction, Choice::new(score, &result));
+
+        let better = best.as_ref().is_none_or(|(best, ..)| {
+            if maximising {
+                score > *best
+            } else {
+                score < *best
+            }
+        });
+        if better {
+            best = Some((score, direction, result));
+        }
+    }
+
+    let (_, direction, mut result) =
+        best.ok_or_else(|| eyre!("every snake has at least one move"))?;
+    result.choices = scores;
+    Ok((direction, result))
+}
+
+/// [`bigbrain`] or [`alphabeta`] with the first moves handed out between
+/// threads. Each thread searches the rest of the tree below a combination of
+/// our move and the next snake's move, and the results are put back together
+/// here. Alpha-beta can't prune across threads, but the shared transposition
+/// table saves them from scoring the same leaves twice.
+fn split_search(
+    game: &Game,
+    ctx: &mut SearchContext,
+) -> Result<Option<BigbrainResult>> {
+    let options = ctx.options;
+    let split = game.snakes.len().min(2);
+    let (snake_index, depth) = if split == game.snakes.len() {
+        (ME, 1)
+    } else {
+        (split, 0)
+    };
+
+    let tasks = joint_moves(game.snakes.iter().take(split), game);
+    let Some(results) = in_parallel(&tasks, ctx, |moves, ctx| {
+        let mut game = game.clone();
+        let mut moves = moves.clone();
+        if options.algorithm == Algorithm::AlphaBeta {
+            alphabeta(
+                &mut game,
+                snake_index,
+                depth,
+                &mut moves,
+                i64::MIN,
+                i64::MAX,
+                ctx,
+            )
+        } else {
+            bigbrain(&mut game, snake_index, depth, &mut moves, ctx)
+        }
+    })?
+    else {
+        return Ok(None);
+    };
+
+    let me = &game.snakes[ME];
+    let mut ours = vec![];
+    for (direction, group) in &tasks
+        .iter()
+        .zip(results)
+        .group_by(|(moves, _)| moves.get(&me.id).copied())
+    {
+        let direction = direction
+            .ok_or_else(|| eyre!("every task starts with our move"))?;
+        let result = match game.snakes.get(1) {
+            Some(next) => {
+                let choices = group.map(|(moves, result)| {
+                    (
+                        moves.get(&next.id).copied().unwrap_or(Direction::Up),
+                        result,
+                    )
+                });
+                let (theirs, result) = pick(game, next, choices, options)?;
+                result.outer(next.id, theirs, 0)?
+            },
+            None => group
+                .map(|(_, result)| result)
+                .next()
+                .ok_or_else(|| eyre!("every group has at least one task"))?,
+        };
+        ours.push((direction, result));
+    }
+
+    let (direction, result) = pick(game, me, ours, options)?;
+    result.outer(me.id, direction, 0).map(Some)
+}
+
+/// [`simultaneous`] with the cells of the first turn's matrix game handed out
+/// between threads.
+fn split_simultaneous(
+    game: &Game,
+    solver: Solver,
+    ctx: &mut SearchContext,
+) -> Result<Option<BigbrainResult>> {
+    if ctx.out_of_time() {
+        return Ok(None);
+    }
+    ctx.nodes += 1;
+
+    let me = find_me(game)?;
+    let (ours, theirs) = team_moves(game, me);
+
+    let tasks: Vec<_> = ours
+        .iter()
+        .flat_map(|our_moves| {
+            theirs.iter().map(|their_moves| {
+                let mut moves = our_moves.clone();
+                moves.extend(their_moves);
+                moves
+            })
+        })
+        .collect();
+    let Some(cells) = in_parallel(&tasks, ctx, |moves, ctx| {
+        matrix_cell(&mut game.clone(), 0, solver, moves, me, ctx)
+    })?
+    else {
+        return Ok(None);
+    };
+
+    let mut cells = cells.into_iter();
+    let rows = ours
+        .iter()
+        .map(|_| cells.by_ref().take(theirs.len()).collect())
+        .collect();
+    Ok(solve_turn(&ours, &theirs, rows, 0, solver, ctx)?
+        .map(|(_, result)| result))
+}
+
+/// Runs a single search of the given game to `options.max_depth`, split
+/// between `options.threads` threads.
+///
+/// # Errors
+///
+/// Can fail if something is wrong with the input data, for example if a snake
+/// has no body.
+pub fn search(
+    game: &Game,
+    ctx: &mut SearchContext,
+) -> Result<Option<BigbrainResult>> {
+    let parallel = ctx.options.threads > 1;
+    // the search plays moves in place, and takes them all back by the end.
+    let mut game = game.clone();
+    let mut moves = Moves::new();
+    match ctx.options.algorithm {
+        Algorithm::MaxN | Algorithm::AlphaBeta if parallel => {
+            split_search(&game, ctx)
+        },
+        Algorithm::MaxN => bigbrain(&mut game, ME, 0, &mut moves, ctx),
+        Algorithm::AlphaBeta => {
+            alphabeta(&mut game, ME, 0, &mut moves, i64::MIN, i64::MAX, ctx)
+        },
+        Algorithm::Simultaneous(solver) if parallel => {
+            split_simultaneous(&game, solver, ctx)
+        },
+        Algorithm::Simultaneous(solver) => simultaneous(&mut game, solver, ctx),
+    }
+}
+
+/// Searches one turn deeper at a time until time runs out, the game is decided
+/// within the search horizon, or `options.max_depth` is reached.
+///
+/// Returns the result of the deepest search that finished, along with the
+/// total number of nodes visited.
+///
+/// # Errors
+///
+/// Can fail if something is wrong with the input data, for example if a snake
+/// has no body.
+pub fn deepen(
+    game: &Game,
+    options: &BigbrainOptions,
+    known_scores: &TranspositionTable,
+    start: Instant,
+) -> Result<(BigbrainResult, u64)> {
+    let mut result = BigbrainResult::inner(&Scores::new(), 0, Variation::new());
+    let mut nodes = 0;
+
+    known_scores.new_search();
+
+    let mut depth = 1;
+    while depth <= options.max_depth && start.elapsed() < options.time_limit {
+        let iteration = BigbrainOptions {
+            max_depth: depth,
+            ..*options
+        };
+        let mut ctx = SearchContext::new(known_scores, start, &iteration, None);
+
+        let new_result = search(game, &mut ctx)?;
+        nodes += ctx.nodes;
+
+        match new_result {
+            Some(new_result) => {
+                result = new_result;
+                if result.depth < depth {
+                    println!(
+                        "bigbrain only got to depth {}/{}, exiting early.",
+                        result.depth, depth
+                    );
+                    break;
+                }
+            },
+            None => break,
+        }
+
+        depth += 1;
+    }
+
+    Ok((result, nodes))
+}
+
+/// Keeps searching the positions we could be in next turn after moving in
+/// `direction`, until `stop` is set or `time_limit` runs out.
+///
+/// Nothing is returned. The point is to fill `known_scores` up, so that the
+/// search for whichever position actually comes along is already warm.
+///
+/// # Errors
+///
+/// Can fail if something is wrong with the input data, for example if a snake
+/// has no body.
+pub fn ponder(
+    game: &Game,
+    direction: Direction,
+    algorithm: Algorithm,
+    weights: Weights,
+    known_scores: &TranspositionTable,
+    stop: &AtomicBool,
+    time_limit: Duration,
+) -> Result<()> {
+    let start = Instant::now();
+    known_scores.new_search();
+
+    let mut predictions = vec![];
+    for mut moves in
+        joint_moves(game.snakes.iter().filter(|s| s.id != ME), game)
+    {
+        moves.insert(ME, direction);
+        let (next, ..) = game.step(&moves)?;
+        // there's nothing to think about if we're dead or the game's over.
+        if next.snakes.first().is_some_and(|snake| snake.id == ME)
+            && !(next.multisnake && next.is_over())
+        {
+            predictions.push(next);
+        }
+    }
+
+    for depth in 1.. {
+        let options = BigbrainOptions {
+            max_depth: depth,
+            time_limit,
+            algorithm,
+            // pondering shares the machine with whatever else is going on.
+            threads: 1,
+            weights,
+        };
+
+        let mut deepest = 0;
+        for next in &predictions {
+            let mut ctx =
+                SearchContext::new(known_scores, start, &options, Some(stop));
+            let Some(result) = search(next, &mut ctx)? else {
+                return Ok(());
+            };
+            deepest = deepest.max(result.depth);
+        }
+
+        // every prediction is decided within the horizon already.
+        if deepest < depth {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use std::{
+        cmp::Reverse,
+        collections::{HashMap, HashSet},
+        time::{Duration, Instant},
+    };
+
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use super::{
+        deepen,
+        describe_choices,
+        joint_moves,
+        Algorithm,
+        BigbrainOptions,
+        BigbrainResult,
+        Moves,
+        Solver,
+        Turn,
+    };
+    use crate::{
+        arena::{new_game, ArenaOptions},
+        fightsnake::types::{Coord, Direction},
+        strategies::strangle::{
+            board::{Board, Topology},
+            game::Game,
+            rules::Rules,
+            score_factors::Weights,
+            snake::Snake,
+            transposition::TranspositionTable,
+            ME,
+        },
+    };
+
+    const DEPTH: u64 = 2;
+
+    /// The moves made on `turn`, in the form a game can play them.
+    fn moves_of(turn: Turn) -> Moves {
+        turn.iter()
+            .enumerate()
+            .filter_map(|(snake_id, direction)| Some((snake_id, (*direction)?)))
+            .collect()
+    }
+
+    /// Searches `game` to `depth` with `algorithm` on `threads` threads.
+    fn search(
+        game: &Game,
+        algorithm: Algorithm,
+        threads: usize,
+        depth: u64,
+    ) -> BigbrainResult {
+        let options = BigbrainOptions {
+            max_depth: depth,
+            time_limit: Duration::MAX,
+            algorithm,
+            threads,
+            weights: Weights::DEFAULT,
+        };
+        let table = TranspositionTable::new(1 << 10);
+        deepen(game, &options, &table, Instant::now())
+            .expect("search failed")
+            .0
+    }
+
+    /// Searches the start of a game between `snakes` snakes with every
+    /// algorithm, on one thread and on two. Each result comes with a note of
+    /// how it was found.
+    fn search_everything(
+        snakes: usize,
+    ) -> (Game, Vec<(String, BigbrainResult)>) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let game = Game::try_from(
+            new_game(snakes, &ArenaOptions::default(), &mut rng)
+                .expect("no game"),
+        )
+        .expect("bad game");
+
+        let mut results = vec![];
+        for algorithm in [
+            Algorithm::MaxN,
+            Algorithm::AlphaBeta,
+            Algorithm::Simultaneous(Solver::MaxMin),
+        ] {
+            for threads in [1, 2] {
+                let result = search(&game, algorithm, threads, DEPTH);
+                results.push((
+                    format!(
+                        "{algorithm:?} on {threads} threads with {snakes} \
+                         snakes"
+                    ),
+                    result,
+                ));
+            }
+        }
+        (game, results)
+    }
+
+    #[test]
+    fn variations_play_out_to_the_reported_scores() {
+        for snakes in [2, 4] {
+            let (game, results) = search_everything(snakes);
+            for (how, result) in results {
+                assert_eq!(result.variation.len() as u64, DEPTH, "{how}");
+                assert_eq!(result.variation[0][ME], result.direction, "{how}");
+
+                let mut position = game.clone();
+                let mut undo = None;
+                for &turn in &result.variation {
+                    let (next, next_undo) = position
+                        .step(&moves_of(turn))
+                        .expect("illegal variation");
+                    position = next;
+                    undo = Some(next_undo);
+                }
+                let scores = position
+                    .scores(&undo.expect("no turns played"))
+                    .expect("couldn't score");
+                assert_eq!(
+                    scores[ME].calculate(DEPTH, &Weights::DEFAULT),
+                    result.scores[ME].calculate(DEPTH, &Weights::DEFAULT),
+                    "{how}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn the_last_food_gets_eaten() {
+        let snake = |id, body: [(i64, i64); 3]| {
+            Snake::new(
+                id,
+                body.iter().map(|&(x, y)| Coord { x, y }).collect(),
+                60,
+                None,
+            )
+        };
+        let game = Game::new(
+            vec![
+                snake(ME, [(5, 5), (5, 4), (5, 3)]),
+                snake(1, [(0, 10), (1, 10), (2, 10)]),
+            ],
+            vec![Coord { x: 5, y: 6 }],
+            vec![],
+            Board {
+                width:    11,
+                height:   11,
+                topology: Topology::Bounded,
+            },
+            Rules::default(),
+        );
+
+        // further ahead there's time to go round and eat it later, but it
+        // still has to get eaten.
+        for algorithm in [Algorithm::MaxN, Algorithm::AlphaBeta] {
+            assert_eq!(
+                search(&game, algorithm, 1, 1).direction,
+                Some(Direction::Up),
+                "{algorithm:?}"
+            );
+            for depth in 1..=3 {
+                let result = search(&game, algorithm, 1, depth);
+                assert_eq!(
+                    result.scores[ME].length, 4,
+                    "{algorithm:?} to depth {depth}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn every_move_we_could_make_gets_a_score() {
+        let (game, results) = search_everything(2);
+        let possible: HashSet<_> = game.snakes[ME]
+            .possible_directions(&game.board)
+            .into_iter()
+            .collect();
+
+        for (how, result) in results {
+            let scored: HashSet<_> = result.choices.keys().copied().collect();
+            assert_eq!(scored, possible, "{how}");
+
+            let direction = result.direction.expect("no move picked");
+            assert_eq!(
+                result.choices.get(&direction).map(|choice| choice.score),
+                result.choices.values().map(|choice| choice.score).max(),
+                "{how}"
+            );
+        }
+    }
+
+    /// Duels from a few different games, each played on a little way from
+    /// the start so we're not always looking at the opening.
+    fn duels() -> Vec<Game> {
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..8)
+            .map(|seed| {
+                let options = ArenaOptions {
+                    seed,
+                    ..ArenaOptions::default()
+                };
+                let mut game = Game::try_from(
+                    new_game(2, &options, &mut rng).expect("no game"),
+                )
+                .expect("bad game");
+
+                for _ in 0..seed * 3 {
+                    let choices = joint_moves(game.snakes.iter(), &game);
+                    let Some(moves) = choices.choose(&mut rng) else {
+                        break;
+                    };
+                    let (next, _) =
+                        game.step_standard(moves, &mut rng).expect("bad step");
+                    if next.snakes.len() < 2 || next.snakes[0].id != ME {
+                        break;
+                    }
+                    game = next;
+                }
+                game
+            })
+            .collect()
+    }
+
+    /// How each of our first moves scored.
+    fn choice_scores(result: &BigbrainResult) -> HashMap<Direction, i64> {
+        result
+            .choices
+            .iter()
+            .map(|(&direction, choice)| (direction, choice.score))
+            .collect()
+    }
+
+    #[test]
+    fn alphabeta_scores_every_move_exactly() {
+        // letting the other snake see our move before it picks its own is what
+        // alpha-beta assumes too, but without cutting anything off.
+        for (i, game) in duels().iter().enumerate() {
+            let max_min = choice_scores(&search(
+                game,
+                Algorithm::Simultaneous(Solver::MaxMin),
+                1,
+                3,
+            ));
+            let alphabeta =
+                choice_scores(&search(game, Algorithm::AlphaBeta, 1, 3));
+
+            let mut ranked: Vec<_> = alphabeta.iter().collect();
+            ranked.sort_by_key(|(_, score)| Reverse(**score));
+            if let Some((second, score)) = ranked.get(1) {
+                assert_eq!(max_min.get(second), Some(*score), "duel {i}");
+            }
+            assert_eq!(alphabeta, max_min, "duel {i}");
+        }
+    }
+
+    #[test]
+    fn alphabeta_agrees_with_max_n_in_duels() {
+        let mut disagreements = vec![];
+        for (i, game) in duels().iter().enumerate() {
+            let max_n = search(game, Algorithm::MaxN, 1, 3);
+            let alphabeta = search(game, Algorithm::AlphaBeta, 1, 3);
+            if alphabeta.direction != max_n.direction {
+                disagreements.push(format!(
+                    "duel {i}: alpha-beta picked {}, max-n picked {}",
+                    describe_choices(&alphabeta.choices),
+                    describe_choices(&max_n.choices),
+                ));
+            }
+
+            // max-n lets the other snake look after itself, which can only
+            // ever be as bad for us as it going out of its way to hurt us.
+            let max_n = choice_scores(&max_n);
+            for (direction, score) in choice_scores(&alphabeta) {
+                let max_n = max_n.get(&direction);
+                assert!(
+                    max_n.is_some_and(|&max_n| score <= max_n),
+                    "duel {i}: alpha-beta scored {direction} {score} but \
+                     max-n scored it {max_n:?}"
+                );
+            }
+        }
+        assert!(disagreements.is_empty(), "{}", disagreements.join("\n"));
+    }
 }
@@ -1,15 +1,17 @@
-use std::{
-    collections::{HashMap, VecDeque},
-    fmt,
-};
+use std::{array, collections::HashMap, fmt};
 
 use color_eyre::{eyre::eyre, Report, Result};
+use rand::{seq::SliceRandom, Rng};
 
 use super::{
-    board::Board,
-    score_factors::ScoreFactors,
-    snake::Snake,
+    bitboard::{squares, Bits, Grid, Heap, Large},
+    board::{Board, Topology},
+    rules::Rules,
+    score_factors::{ScoreFactors, Scores, Territory, MAX_SNAKES},
+    snake::{Saved, Snake},
+    zobrist::{self, Key},
     SnakeID,
+    SquadID,
     ME,
 };
 use crate::{
@@ -17,11 +19,14 @@ use crate::{
         constants::MAX_HEALTH,
         models::GameState,
         types::{Coord, Direction},
-        utils::manhattan_distance,
     },
     strategies::strangle::score_factors::DeathKind,
 };
 
+/// Full health, for counting down from in flood fills.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+const FULL_HEALTH: usize = MAX_HEALTH as usize;
+
 pub enum Type {
     Solo,
     Duel,
@@ -32,36 +37,126 @@ pub enum Type {
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub struct Game {
-    pub snakes:      Vec<Snake>,
-    pub prev_snakes: Vec<Snake>,
-    pub food:        Vec<Coord>,
-    pub prev_food:   Vec<Coord>,
-    pub hazards:     Vec<Coord>,
-    pub board:       Board,
-    pub multisnake:  bool,
+    pub snakes:     Vec<Snake>,
+    pub food:       Vec<Coord>,
+    pub hazards:    Vec<Coord>,
+    pub board:      Board,
+    pub rules:      Rules,
+    pub multisnake: bool,
+    /// the part of the zobrist key that doesn't belong to any snake, which is
+    /// the food and hazards.
+    pub board_key:  Key,
+}
+
+/// Everything [`Game::make`] changed, so that [`Game::unmake`] can put it
+/// back.
+///
+/// Records are meant to be reused. Their buffers keep hold of their capacity,
+/// so once a search has warmed up, making and unmaking moves doesn't allocate.
+#[derive(Clone, Debug, Default)]
+pub struct Undo {
+    /// every snake from before the turn, in the order they were in.
+    snakes:      Vec<Saved>,
+    /// the snakes that were eliminated, and where they were in the list when
+    /// they went.
+    fallen:      Vec<(usize, Snake)>,
+    /// the food that was eaten, and where it was in the list.
+    eaten:       Vec<(usize, Coord)>,
+    board_key:   Key,
+    /// how each of the fallen snakes died.
+    death_kinds: HashMap<SnakeID, DeathKind>,
+}
+
+impl Undo {
+    fn clear(&mut self) {
+        self.snakes.clear();
+        self.fallen.clear();
+        self.eaten.clear();
+        self.death_kinds.clear();
+    }
+
+    /// The snakes that were eliminated during the turn, as they were when they
+    /// died, and how they died.
+    pub fn deaths(&self) -> impl Iterator<Item = (&Snake, DeathKind)> {
+        self.fallen.iter().filter_map(|(_, snake)| {
+            self.death_kinds.get(&snake.id).map(|&kind| (snake, kind))
+        })
+    }
 }
 
 impl Game {
+    #[must_use]
     pub fn new(
         snakes: Vec<Snake>,
         food: Vec<Coord>,
         hazards: Vec<Coord>,
         board: Board,
+        rules: Rules,
     ) -> Self {
         let multisnake = snakes.len() > 1;
-        let prev_snakes = snakes.clone();
-        let prev_food = food.clone();
+        let board_key = food
+            .iter()
+            .map(|&c| zobrist::food(c))
+            .chain(hazards.iter().map(|&c| zobrist::hazard(c)))
+            .fold(0, Key::wrapping_add);
         Self {
             snakes,
-            prev_snakes,
             food,
-            prev_food,
             hazards,
             board,
+            rules,
             multisnake,
+            board_key,
         }
     }
 
+    /// The zobrist key for the current position, put together from the keys
+    /// that [`Self::step`] keeps up to date as the snakes move.
+    #[must_use]
+    pub fn key(&self) -> Key {
+        self.snakes
+            .iter()
+            .map(Snake::key)
+            .fold(self.board_key, Key::wrapping_add)
+    }
+
+    /// The game from `snake_id`'s point of view, with them first in line and
+    /// playing as [`ME`]. They swap ids with whoever was [`ME`], and everyone
+    /// else stays as they were. Returns nothing if `snake_id` isn't playing.
+    #[must_use]
+    pub fn seen_by(&self, snake_id: SnakeID) -> Option<Self> {
+        let position =
+            self.snakes.iter().position(|snake| snake.id == snake_id)?;
+        let mut snakes: Vec<Snake> = self
+            .snakes
+            .iter()
+            .map(|snake| {
+                let id = match snake.id {
+                    id if id == snake_id => ME,
+                    ME => snake_id,
+                    id => id,
+                };
+                Snake::new(id, snake.body.clone(), snake.health, snake.squad)
+            })
+            .collect();
+        snakes.swap(0, position);
+
+        Some(Self {
+            multisnake: self.multisnake,
+            ..Self::new(
+                snakes,
+                self.food.clone(),
+                self.hazards.clone(),
+                self.board.clone(),
+                self.rules.clone(),
+            )
+        })
+    }
+
+    /// # Panics
+    ///
+    /// Panics if there are no snakes left in the game.
+    #[must_use]
     pub fn game_type(&self) -> Type {
         assert!(!self.snakes.is_empty(), "no game can have zero snakes");
         match self.snakes.len() {
@@ -73,66 +168,90 @@ impl Game {
         }
     }
 
+    /// Whether the game has been won, which is when only one team is left.
+    #[must_use]
+    pub fn is_over(&self) -> bool {
+        self.snakes
+            .first()
+            .is_none_or(|first| self.snakes.iter().all(|s| first.is_ally(s)))
+    }
+
+    /// Advances the game by one turn for the purposes of searching, without
+    /// touching the original. Returns the new game along with the record of
+    /// what happened, which [`Self::scores`] needs.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a snake has no body.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there isn't exactly one move per snake.
     pub fn step(
         &self,
         moves: &HashMap<SnakeID, Direction>,
-    ) -> Result<(Self, Vec<bool>, HashMap<SnakeID, DeathKind>)> {
+    ) -> Result<(Self, Undo)> {
         assert!(moves.len() == self.snakes.len(), "wrong number of moves");
 
         let mut step = self.clone();
+        let mut undo = Undo::default();
+        step.make(moves, &mut undo)?;
+        Ok((step, undo))
+    }
+
+    /// Advances the game by one turn in place for the purposes of searching,
+    /// filling `undo` in so that [`Self::unmake`] can take the turn back.
+    ///
+    /// This deliberately bends the rules in our disfavour: we lose every
+    /// equal-length head-to-head, and no food ever spawns. Use
+    /// [`Self::step_standard`] to play a turn out faithfully.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a snake has no body, in which case the game is left as it was.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a snake doesn't have a move.
+    pub fn make(
+        &mut self,
+        moves: &HashMap<SnakeID, Direction>,
+        undo: &mut Undo,
+    ) -> Result<()> {
+        if self.snakes.iter().any(|snake| snake.body.is_empty()) {
+            return Err(eyre!("snake without a body"));
+        }
+
+        undo.clear();
+        undo.board_key = self.board_key;
 
         // step 1 - move snakes
-        for snake in &mut step.snakes {
+        for snake in &mut self.snakes {
             let direction = *moves.get(&snake.id).unwrap_or_else(|| {
                 panic!("snake #{} didn't provide a move", snake.id)
             });
 
-            snake.body.pop_back();
-            snake.body.push_front(
-                snake
-                    .body
-                    .front()
-                    .ok_or(eyre!("snake without a body"))?
-                    .neighbour(direction),
-            );
-            snake.health -= 1;
+            undo.snakes.push(snake.save());
+            snake.slither(self.board.neighbour(snake.body[0], direction));
+            snake.set_health(snake.health - 1);
         }
 
-        let freespace = step.calculate_free_space()?;
+        self.damage_hazards();
 
         // step 2 - remove eliminated battlesnakes
-        let mut death_kind_map = HashMap::new();
-
-        step.prev_snakes.clear();
-        step.prev_snakes.extend_from_slice(&step.snakes);
-
-        step.snakes.retain(|snake| {
-            if snake.health <= 0 {
-                death_kind_map.insert(snake.id, DeathKind::Normal);
-                return false;
-            }
-
-            #[allow(clippy::expect_used)] // inside retain again...
-            if let Some(index) = self
-                .freespace_index(snake.body[0])
-                .expect("invalid freespace index")
-            {
-                if !freespace[index] {
-                    death_kind_map.insert(snake.id, DeathKind::Normal);
-                    return false;
-                }
-            } else {
-                death_kind_map.insert(snake.id, DeathKind::Normal);
-                return false;
-            }
-
-            true
-        });
+        let squares = self.board.squares();
+        if squares <= u128::CAPACITY {
+            self.eliminate::<u128>(undo);
+        } else if squares <= Large::CAPACITY {
+            self.eliminate::<Large>(undo);
+        } else {
+            self.eliminate::<Heap>(undo);
+        }
+        self.remove_fallen(undo);
 
         // step 2a resolve head-to-head collisions
-        let mut keep = vec![true; step.snakes.len()];
-        for (ai, a) in step.snakes.iter().enumerate() {
-            for (bi, b) in step.snakes.iter().enumerate().skip(ai + 1) {
+        for (ai, a) in self.snakes.iter().enumerate() {
+            for b in self.snakes.iter().skip(ai + 1) {
                 if a.body[0] == b.body[0] {
                     // horrible hack alert!
                     // we pretend that only we can die by same-size head-to-head
@@ -144,83 +263,389 @@ impl Game {
                         // b can never be ME, because i'm always first in the
                         // list.
                         if a.id == ME {
-                            death_kind_map.insert(a.id, DeathKind::Honourable);
-                            keep[ai] = false;
+                            undo.death_kinds
+                                .insert(a.id, DeathKind::Honourable);
                         }
                     } else {
                         if b.body.len() >= a.body.len() {
-                            death_kind_map.insert(a.id, DeathKind::Honourable);
-                            keep[ai] = false;
+                            undo.death_kinds
+                                .insert(a.id, DeathKind::Honourable);
                         }
                         if a.body.len() >= b.body.len() {
-                            death_kind_map.insert(b.id, DeathKind::Honourable);
-                            keep[bi] = false;
+                            undo.death_kinds
+                                .insert(b.id, DeathKind::Honourable);
                         }
                     }
                 }
             }
         }
-        let mut kill_iter = keep.iter();
-
-        // FIXME: is there a clean way to bubble up results from
-        // retain?
-        #[allow(clippy::expect_used)]
-        step.snakes.retain(|_| {
-            *kill_iter
-                .next()
-                .expect("kill_iter must be the same length as step.snakes")
-        });
+        self.remove_fallen(undo);
 
         // step 3 - eat food
-        step.prev_food.clear();
-        step.prev_food.extend_from_slice(&step.food);
-        step.food.retain(|food| {
-            for snake in &mut step.snakes {
-                if snake.body[0] == *food {
-                    snake.health = MAX_HEALTH;
-
-                    // FIXME: is there a clean way to bubble up results from
-                    // retain?
-                    #[allow(clippy::expect_used)]
-                    snake.body.push_back(
-                        *snake.body.back().expect("snake has no body"),
-                    );
-                    return false;
-                }
+        let mut index = 0;
+        while index < self.food.len() {
+            let food = self.food[index];
+            if let Some(snake) =
+                self.snakes.iter_mut().find(|snake| snake.body[0] == food)
+            {
+                snake.set_health(MAX_HEALTH);
+                snake.grow();
+                self.board_key =
+                    self.board_key.wrapping_sub(zobrist::food(food));
+                undo.eaten.push((index, self.food.remove(index)));
+            } else {
+                index += 1;
             }
-            true
-        });
+        }
 
         // step 4 - spawn new food
         // we can't predict this. we assume none will spawn, and if it does then
         // we'll adapt to it on the next real turn.
 
-        Ok((step, freespace, death_kind_map))
+        self.share_squad_fate(undo);
+        self.constrict();
+
+        Ok(())
+    }
+
+    /// Takes back the turn that [`Self::make`] recorded in `undo`.
+    pub fn unmake(&mut self, undo: &mut Undo) {
+        for (index, snake) in undo.fallen.drain(..).rev() {
+            self.snakes.insert(index, snake);
+        }
+        for (index, food) in undo.eaten.drain(..).rev() {
+            self.food.insert(index, food);
+        }
+        for (snake, saved) in self.snakes.iter_mut().zip(undo.snakes.drain(..))
+        {
+            snake.restore(saved);
+        }
+        self.board_key = undo.board_key;
+    }
+
+    /// Marks everyone who starved, left the board or ran into a body as dead
+    /// in `undo`. Everyone's body is in the way, even if they're about to be
+    /// eliminated themselves.
+    fn eliminate<B: Bits>(&self, undo: &mut Undo) {
+        let bodies: B = squares(
+            &self.board,
+            self.snakes
+                .iter()
+                .flat_map(|snake| snake.body.iter().skip(1).copied()),
+        );
+        let passable = self
+            .rules
+            .squad
+            .is_some_and(|squad| squad.allow_body_collisions);
+
+        for snake in &self.snakes {
+            let head: B = squares(&self.board, [snake.body[0]]);
+            // team-mates' bodies only matter once we know we hit something.
+            let crashed = !head.and(&bodies).is_empty()
+                && (!passable
+                    || self
+                        .snakes
+                        .iter()
+                        .any(|other| self.runs_into(snake, other)));
+            if snake.health <= 0 || head.is_empty() || crashed {
+                undo.death_kinds.insert(snake.id, DeathKind::Normal);
+            }
+        }
+    }
+
+    /// Takes every snake with a death kind in `undo` out of the game, and
+    /// keeps hold of them in `undo`.
+    fn remove_fallen(&mut self, undo: &mut Undo) {
+        let mut index = 0;
+        while index < self.snakes.len() {
+            if undo.death_kinds.contains_key(&self.snakes[index].id) {
+                undo.fallen.push((index, self.snakes.remove(index)));
+            } else {
+                index += 1;
+            }
+        }
     }
 
-    pub fn score(
+    /// Advances the game by one turn exactly as the official standard ruleset
+    /// does, using `rng` to spawn food.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a snake has no body or is missing a move.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of moves doesn't match the number of snakes.
+    pub fn step_standard<R: Rng>(
         &self,
-        snake: &Snake,
-        freespace: &[bool],
-        death_kind: DeathKind,
-    ) -> Result<ScoreFactors> {
-        if !self.snakes.contains(snake) {
-            // we really don't want to die
-            return Ok(ScoreFactors::dead(
-                snake.id,
-                death_kind,
-                self.multisnake,
-            ));
+        moves: &HashMap<SnakeID, Direction>,
+        rng: &mut R,
+    ) -> Result<(Self, HashMap<SnakeID, DeathKind>)> {
+        assert!(moves.len() == self.snakes.len(), "wrong number of moves");
+
+        let mut step = self.clone();
+
+        // step 1 - move snakes and reduce their health
+        for snake in &mut step.snakes {
+            let direction = *moves.get(&snake.id).ok_or_else(|| {
+                eyre!("snake #{} didn't provide a move", snake.id)
+            })?;
+
+            snake.slither(
+                step.board.neighbour(
+                    *snake
+                        .body
+                        .front()
+                        .ok_or_else(|| eyre!("snake without a body"))?,
+                    direction,
+                ),
+            );
+            snake.set_health(snake.health - 1);
+        }
+
+        // step 2 - hazards hurt anyone standing in them
+        step.damage_hazards();
+
+        // step 3 - feed snakes. everyone with their head on a food eats it,
+        // even if they're about to be eliminated.
+        for snake in &mut step.snakes {
+            let head = snake.body[0];
+            if step.food.contains(&head) {
+                snake.set_health(MAX_HEALTH);
+                snake.grow();
+            }
+        }
+        step.food.retain(|food| {
+            let eaten = step.snakes.iter().any(|snake| snake.body[0] == *food);
+            if eaten {
+                step.board_key =
+                    step.board_key.wrapping_sub(zobrist::food(*food));
+            }
+            !eaten
+        });
+
+        // step 4 - remove eliminated battlesnakes
+        let mut undo = Undo {
+            death_kinds: step.eliminations(),
+            ..Undo::default()
+        };
+        step.remove_fallen(&mut undo);
+
+        // step 5 - team-mates share what happened to them
+        step.share_squad_fate(&mut undo);
+
+        // step 6 - constrictor snakes grow no matter what
+        step.constrict();
+
+        // step 7 - spawn new food, now that the fallen are out of the way
+        step.spawn_food(rng);
+
+        Ok((step, undo.death_kinds))
+    }
+
+    /// In constrictor games every surviving snake grows and has its health
+    /// reset at the end of each turn.
+    fn constrict(&mut self) {
+        if !self.rules.constrictor {
+            return;
+        }
+        for snake in &mut self.snakes {
+            snake.set_health(MAX_HEALTH);
+            snake.grow();
+        }
+    }
+
+    /// Hurts every snake whose head is in a hazard, unless they're about to
+    /// eat. Stacked hazards hurt once per stack.
+    fn damage_hazards(&mut self) {
+        for snake in &mut self.snakes {
+            let head = snake.body[0];
+            if self.food.contains(&head) {
+                continue;
+            }
+            let stacks = self.hazards.iter().filter(|h| **h == head).count();
+            for _ in 0..stacks {
+                snake.set_health(
+                    (snake.health - self.rules.hazard_damage).max(0),
+                );
+            }
+        }
+    }
+
+    /// Works out who gets eliminated this turn, following the order that the
+    /// official rules use.
+    ///
+    /// Snakes that starve or leave the board are removed first and can't be
+    /// collided with. Everyone else is then checked against the remaining
+    /// snakes all at once, so two snakes can eliminate each other.
+    fn eliminations(&self) -> HashMap<SnakeID, DeathKind> {
+        let mut death_kind_map = HashMap::new();
+
+        for snake in &self.snakes {
+            if snake.health <= 0 || !self.board.contains(snake.body[0]) {
+                death_kind_map.insert(snake.id, DeathKind::Normal);
+            }
+        }
+
+        let survivors: Vec<&Snake> = self
+            .snakes
+            .iter()
+            .filter(|snake| !death_kind_map.contains_key(&snake.id))
+            .collect();
+
+        let mut collisions = HashMap::new();
+        for snake in &survivors {
+            let head = snake.body[0];
+            if survivors.iter().any(|other| self.runs_into(snake, other)) {
+                collisions.insert(snake.id, DeathKind::Normal);
+            } else if survivors.iter().any(|other| {
+                other.id != snake.id
+                    && other.body[0] == head
+                    && snake.body.len() <= other.body.len()
+            }) {
+                collisions.insert(snake.id, DeathKind::Honourable);
+            }
+        }
+
+        death_kind_map.extend(collisions);
+        death_kind_map
+    }
+
+    /// Whether `snake`'s head has run into `other`'s body. Squad games may let
+    /// team-mates pass through each other, but never through themselves.
+    fn runs_into(&self, snake: &Snake, other: &Snake) -> bool {
+        if other.id != snake.id
+            && snake.is_ally(other)
+            && self
+                .rules
+                .squad
+                .is_some_and(|squad| squad.allow_body_collisions)
+        {
+            return false;
+        }
+        other.body.iter().skip(1).any(|c| *c == snake.body[0])
+    }
+
+    /// Applies the squad rules once eliminations are done: team-mates may fall
+    /// together, and share the best health and length among them.
+    fn share_squad_fate(&mut self, undo: &mut Undo) {
+        let Some(squad_rules) = self.rules.squad else {
+            return;
+        };
+
+        if squad_rules.shared_elimination {
+            for snake in &self.snakes {
+                if let Some(squad) = snake.squad
+                    && undo
+                        .fallen
+                        .iter()
+                        .any(|(_, fallen)| fallen.squad == Some(squad))
+                {
+                    undo.death_kinds.insert(snake.id, DeathKind::Normal);
+                }
+            }
+            self.remove_fallen(undo);
+        }
+
+        let mut best: HashMap<SquadID, (i64, usize)> = HashMap::new();
+        for snake in &self.snakes {
+            if let Some(squad) = snake.squad {
+                let entry = best.entry(squad).or_default();
+                entry.0 = entry.0.max(snake.health);
+                entry.1 = entry.1.max(snake.body.len());
+            }
         }
 
+        for snake in &mut self.snakes {
+            let Some((health, length)) =
+                snake.squad.and_then(|squad| best.get(&squad))
+            else {
+                continue;
+            };
+            if squad_rules.shared_health {
+                snake.set_health(*health);
+            }
+            if squad_rules.shared_length {
+                snake.set_length(*length);
+            }
+        }
+    }
+
+    /// Spawns food the way the standard ruleset does: top up to the minimum,
+    /// otherwise maybe spawn one more. Constrictor games clear away whatever
+    /// food is left instead.
+    fn spawn_food<R: Rng>(&mut self, rng: &mut R) {
+        if self.rules.constrictor {
+            for food in self.food.drain(..) {
+                self.board_key =
+                    self.board_key.wrapping_sub(zobrist::food(food));
+            }
+            return;
+        }
+
+        let current = self.food.len() as u64;
+        let wanted = if current < self.rules.minimum_food {
+            self.rules.minimum_food - current
+        } else {
+            u64::from(
+                self.rules.food_spawn_chance > 0
+                    && 100 - rng.gen_range(0..100)
+                        < self.rules.food_spawn_chance,
+            )
+        };
+
+        if wanted == 0 {
+            return;
+        }
+
+        let unoccupied = self.unoccupied_points();
+        let spawned = unoccupied.choose_multiple(
+            rng,
+            usize::try_from(wanted).unwrap_or(usize::MAX),
+        );
+        for &food in spawned {
+            self.board_key = self.board_key.wrapping_add(zobrist::food(food));
+            self.food.push(food);
+        }
+    }
+
+    /// Every square that food is allowed to spawn on. That excludes snakes,
+    /// food, hazards, and anywhere a snake could move next turn.
+    fn unoccupied_points(&self) -> Vec<Coord> {
+        let mut points = Vec::new();
+        for y in 0..self.board.height {
+            for x in 0..self.board.width {
+                let coord = Coord { x, y };
+                let occupied = self.food.contains(&coord)
+                    || self.hazards.contains(&coord)
+                    || self.snakes.iter().any(|snake| {
+                        snake.body.contains(&coord)
+                            || Direction::iter().any(|d| {
+                                self.board.neighbour(snake.body[0], *d) == coord
+                            })
+                    });
+                if !occupied {
+                    points.push(coord);
+                }
+            }
+        }
+        points
+    }
+
+    fn score<B: Bits>(
+        &self,
+        snake: &Snake,
+        terrain: &Terrain<B>,
+        territory: Territory,
+    ) -> ScoreFactors {
         // floodfill is too expensive to run with more than 4 snakes.
         let available_squares = if self.snakes.len() <= 4 {
-            self.floodfill(freespace, snake.body[0])?
+            self.floodfill(terrain, snake.body[0], snake.health)
         } else {
             0
         };
 
-        let center_dist = manhattan_distance(
+        let center_dist = self.board.distance(
             snake.body[0],
             Coord {
                 x: self.board.width / 2,
@@ -228,77 +653,276 @@ impl Game {
             },
         );
 
-        Ok(ScoreFactors::alive(
-            snake.id,
-            snake.health,
-            snake.body.len() as i64,
+        let remaining_opponents = self
+            .snakes
+            .iter()
+            .filter(|other| !snake.is_ally(other))
+            .count();
+
+        ScoreFactors::alive(
+            snake,
             center_dist,
-            self.snakes.len() as i64 - 1,
+            remaining_opponents as i64,
             available_squares,
+            territory,
             self.multisnake,
-        ))
+            self.rules.constrictor,
+        )
     }
 
-    fn index(&self, c: Coord) -> Result<usize> {
-        Ok(usize::try_from(c.x + c.y * self.board.width)?)
-    }
+    /// Scores every snake that was in the game before the turn recorded in
+    /// `undo`. Snakes that died during the turn get a dead score.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there are more than [`MAX_SNAKES`] snakes to score.
+    pub fn scores(&self, undo: &Undo) -> Result<Scores> {
+        if self.snakes.len() + undo.fallen.len() > MAX_SNAKES {
+            return Err(eyre!(
+                "only {MAX_SNAKES} snakes can be scored at once"
+            ));
+        }
 
-    fn freespace_index(&self, coord: Coord) -> Result<Option<usize>> {
-        if self.board.contains(coord) {
-            Ok(Some(self.index(coord)?))
+        let squares = self.board.squares();
+        if squares <= u128::CAPACITY {
+            Ok(self.scores_with::<u128>(undo))
+        } else if squares <= Large::CAPACITY {
+            Ok(self.scores_with::<Large>(undo))
         } else {
-            Ok(None)
+            Ok(self.scores_with::<Heap>(undo))
         }
     }
 
-    fn calculate_free_space(&self) -> Result<Vec<bool>> {
-        let mut freespace =
-            vec![true; usize::try_from(self.board.width * self.board.height)?];
+    fn scores_with<B: Bits>(&self, undo: &Undo) -> Scores {
+        let terrain: Terrain<B> = self.terrain(undo);
+        let territories = self.territories(&terrain);
 
-        for snake in &self.snakes {
-            for part in snake.body.iter().skip(1) {
-                if let Some(index) = self.freespace_index(*part)? {
-                    freespace[index] = false;
-                }
-            }
+        // score snakes still in the game
+        let mut scores = Scores::new();
+        for (snake, territory) in self.snakes.iter().zip(territories) {
+            scores.insert(self.score(snake, &terrain, territory));
         }
 
-        for hazard in &self.hazards {
-            freespace[self
-                .freespace_index(*hazard)?
-                .ok_or(eyre!("hazards should never be off the board!"))?] =
-                false;
+        // add bad scores for anyone who died
+        for (snake, death_kind) in undo.deaths() {
+            scores.insert(ScoreFactors::dead(
+                snake,
+                death_kind,
+                self.multisnake,
+            ));
+        }
+
+        scores
+    }
+
+    /// The board as it is after the turn recorded in `undo`. Snakes that died
+    /// during the turn still take up space, since they were in the way when it
+    /// happened.
+    fn terrain<B: Bits>(&self, undo: &Undo) -> Terrain<B> {
+        let grid: Grid<B> = Grid::new(&self.board);
+
+        let fallen = undo.fallen.iter().map(|(_, snake)| snake);
+        let bodies: B = squares(
+            &self.board,
+            self.snakes
+                .iter()
+                .chain(fallen)
+                .flat_map(|snake| snake.body.iter().skip(1).copied()),
+        );
+
+        let mut hazards: Vec<B> = vec![];
+        for &hazard in &self.hazards {
+            let square: B = squares(&self.board, [hazard]);
+            if square.is_empty() {
+                continue;
+            }
+            // each stack goes on the first layer that doesn't have one there
+            // yet.
+            match hazards
+                .iter_mut()
+                .find(|layer| layer.and(&square).is_empty())
+            {
+                Some(layer) => *layer = layer.or(&square),
+                None => hazards.push(square),
+            }
         }
 
-        Ok(freespace)
+        Terrain {
+            free: grid.all.without(&bodies),
+            food: squares(&self.board, self.food.iter().copied()),
+            hazards,
+            grid,
+        }
     }
 
-    fn is_space_free(&self, freespace: &[bool], coord: Coord) -> Result<bool> {
-        Ok(self
-            .freespace_index(coord)?
-            .map_or(false, |index| freespace[index]))
+    /// Counts the squares a snake could reach from `seed` before running out
+    /// of health.
+    ///
+    /// Every move costs a point of health and hazards cost their damage on top
+    /// of that, while food tops health back up. This means a snake might only
+    /// be able to cross part of a hazard zone, or none of it at all.
+    fn floodfill<B: Bits>(
+        &self,
+        terrain: &Terrain<B>,
+        seed: Coord,
+        health: i64,
+    ) -> i64 {
+        let seed: B = squares(&self.board, [seed]);
+        let health = usize::try_from(health.min(MAX_HEALTH)).unwrap_or(0);
+        if health == 0 {
+            return i64::from(seed.count());
+        }
+
+        let (reached, mut sources) = self.spread(terrain, &seed, health);
+
+        // we can carry on from any food we reach with full health. that might
+        // get us to more food, which is just as good a place to start from.
+        let mut refuelled = B::EMPTY;
+        while !sources.is_empty() {
+            let (more, food) = self.spread(terrain, &sources, FULL_HEALTH);
+            refuelled = more;
+            if food.without(&sources).is_empty() {
+                break;
+            }
+            sources = sources.or(&food);
+        }
+
+        i64::from(reached.or(&refuelled).count())
     }
 
-    fn floodfill(&self, freespace: &[bool], seed: Coord) -> Result<i64> {
-        let size = usize::try_from(self.board.width * self.board.height)?;
-        let mut visited = vec![false; size];
-        let mut queue = VecDeque::with_capacity(size);
-        queue.push_back(seed);
+    /// Splits the board up between the snakes, giving each square to whoever
+    /// can get their head there first. Returns what each snake ends up with,
+    /// in the same order as [`Self::snakes`].
+    ///
+    /// Squares that snakes get to at the same time go to the longest of them,
+    /// since they'd win the head-to-head, or to nobody if there's a tie.
+    ///
+    /// There can't be more than [`MAX_SNAKES`] snakes, and everything past
+    /// the last snake is left empty.
+    fn territories<B: Bits>(
+        &self,
+        terrain: &Terrain<B>,
+    ) -> [Territory; MAX_SNAKES] {
+        let mut reached: [B; MAX_SNAKES] = array::from_fn(|index| {
+            self.snakes
+                .get(index)
+                .map_or(B::EMPTY, |snake| squares(&self.board, [snake.body[0]]))
+        });
+        let mut fronts = reached.clone();
+        let mut territories = [Territory::default(); MAX_SNAKES];
+        let mut claimed = B::EMPTY;
+
+        for distance in 0.. {
+            for (index, snake) in self.snakes.iter().enumerate() {
+                fronts[index] = self
+                    .snakes
+                    .iter()
+                    .zip(&reached)
+                    .enumerate()
+                    .filter(|(other_index, (other, _))| {
+                        *other_index != index
+                            && other.body.len() >= snake.body.len()
+                    })
+                    .fold(reached[index].clone(), |kept, (_, (_, theirs))| {
+                        kept.without(theirs)
+                    });
 
-        while let Some(c) = queue.pop_front() {
-            visited[self.index(c)?] = true;
-            for d in Direction::iter() {
-                let neighbour = c.neighbour(*d);
-                if self.is_space_free(freespace, neighbour)?
-                    && !visited[self.index(neighbour)?]
+                let territory = &mut territories[index];
+                territory.squares += i64::from(fronts[index].count());
+                if territory.nearest_food.is_none()
+                    && !fronts[index].and(&terrain.food).is_empty()
                 {
-                    queue.push_back(neighbour);
+                    territory.nearest_food = Some(distance);
                 }
             }
+
+            // contested squares are nobody's, but nobody can get past them
+            // either.
+            claimed = reached.iter().fold(claimed, |claimed, r| claimed.or(r));
+            if fronts.iter().all(Bits::is_empty) {
+                break;
+            }
+
+            for (next, front) in reached.iter_mut().zip(&fronts) {
+                *next = terrain
+                    .grid
+                    .neighbours(front)
+                    .and(&terrain.free)
+                    .without(&claimed);
+            }
         }
 
-        Ok(visited.iter().filter(|v| **v).count() as i64)
+        territories
     }
+
+    /// Spreads out from `sources` with `health` to spend. Returns every square
+    /// reached, along with the food among them, which is as far as this goes
+    /// since eating changes everything.
+    ///
+    /// Squares waiting to be spread from are kept in one set per amount of
+    /// health left, and the sets are worked through from the most health down.
+    /// That way each square is reached with as much health as possible, and
+    /// a whole set of squares spreads out at once.
+    fn spread<B: Bits>(
+        &self,
+        terrain: &Terrain<B>,
+        sources: &B,
+        health: usize,
+    ) -> (B, B) {
+        let damage = usize::try_from(self.rules.hazard_damage).unwrap_or(0);
+
+        let mut waiting = [B::EMPTY; FULL_HEALTH + 1];
+        waiting[health] = sources.clone();
+        let mut lowest = health;
+        let mut reached = B::EMPTY;
+        let mut food = B::EMPTY;
+
+        for left in (1..=health).rev() {
+            if left < lowest {
+                break;
+            }
+            let here = waiting[left].without(&reached);
+            if here.is_empty() {
+                continue;
+            }
+            reached = reached.or(&here);
+
+            let next = terrain
+                .grid
+                .neighbours(&here)
+                .and(&terrain.free)
+                .without(&reached);
+            let eaten = next.and(&terrain.food);
+            food = food.or(&eaten);
+            reached = reached.or(&eaten);
+
+            // each layer of hazards costs a little more to move into.
+            let mut rest = next.without(&terrain.food);
+            let mut cost = 1;
+            for layer in terrain.hazards.iter().chain([&B::EMPTY]) {
+                let band = rest.without(layer);
+                rest = rest.and(layer);
+                if cost < left && !band.is_empty() {
+                    waiting[left - cost] = waiting[left - cost].or(&band);
+                    lowest = lowest.min(left - cost);
+                }
+                cost += damage;
+            }
+        }
+
+        (reached, food)
+    }
+}
+
+/// Everything a flood fill needs to know about the board after a turn.
+struct Terrain<B> {
+    grid:    Grid<B>,
+    /// squares that aren't taken up by a body.
+    free:    B,
+    food:    B,
+    /// one layer per stack of hazards, so the `n`th layer holds every square
+    /// with more than `n` hazards on it.
+    hazards: Vec<B>,
 }
 
 impl TryFrom<GameState> for Game {
@@ -316,22 +940,37 @@ impl TryFrom<GameState> for Game {
         let mut snakes = state.board.snakes;
         snakes.swap(ME, you_idx);
 
+        // squads are named by the engine, but a small id is enough for us.
+        let mut squads: Vec<String> = vec![];
+
+        let rules = Rules::from(&state.game.ruleset);
+        let topology = Topology::from(&state.game.ruleset);
+
         Ok(Self::new(
             snakes
                 .into_iter()
                 .enumerate()
-                .map(|(id, snake)| Snake {
-                    id,
-                    body: snake.body,
-                    health: snake.health,
+                .map(|(id, snake)| {
+                    let squad = (!snake.squad.is_empty()).then(|| {
+                        squads
+                            .iter()
+                            .position(|squad| *squad == snake.squad)
+                            .unwrap_or_else(|| {
+                                squads.push(snake.squad.clone());
+                                squads.len() - 1
+                            })
+                    });
+                    Snake::new(id, snake.body, snake.health, squad)
                 })
                 .collect(),
             state.board.food,
             state.board.hazards,
             Board {
-                width:  state.board.width,
+                width: state.board.width,
                 height: state.board.height,
+                topology,
             },
+            rules,
         ))
     }
 }
@@ -353,3 +992,655 @@ impl fmt::Display for Game {
         Ok(())
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use std::{
+        collections::{HashMap, VecDeque},
+        iter,
+    };
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{
+        Board,
+        DeathKind,
+        Game,
+        Key,
+        Rules,
+        Snake,
+        SnakeID,
+        SquadID,
+        Topology,
+        Undo,
+    };
+    use crate::{
+        fightsnake::{
+            constants::MAX_HEALTH,
+            types::{Coord, Direction},
+        },
+        strategies::strangle::rules::SquadRules,
+    };
+
+    fn snake(id: SnakeID, health: i64, body: &[(i64, i64)]) -> Snake {
+        Snake::new(
+            id,
+            body.iter().map(|&(x, y)| Coord { x, y }).collect(),
+            health,
+            None,
+        )
+    }
+
+    fn game(snakes: Vec<Snake>, food: &[(i64, i64)]) -> Game {
+        Game::new(
+            snakes,
+            food.iter().map(|&(x, y)| Coord { x, y }).collect(),
+            vec![],
+            Board {
+                width:    11,
+                height:   11,
+                topology: Topology::Bounded,
+            },
+            Rules {
+                food_spawn_chance: 0,
+                minimum_food: 0,
+                ..Rules::default()
+            },
+        )
+    }
+
+    fn step(
+        game: &Game,
+        moves: &[Direction],
+    ) -> (Game, HashMap<SnakeID, DeathKind>) {
+        let moves = moves.iter().copied().enumerate().collect();
+        game.step_standard(&moves, &mut StdRng::seed_from_u64(0))
+            .expect("step failed")
+    }
+
+    fn body(game: &Game, id: SnakeID) -> VecDeque<Coord> {
+        game.snakes
+            .iter()
+            .find(|snake| snake.id == id)
+            .expect("snake is missing")
+            .body
+            .clone()
+    }
+
+    /// How many squares our snake can reach with the given health.
+    fn reachable(game: &Game, health: i64) -> i64 {
+        let terrain = game.terrain::<u128>(&Undo::default());
+        game.floodfill(&terrain, game.snakes[0].body[0], health)
+    }
+
+    #[test]
+    fn starving_snakes_are_eliminated() {
+        let game = game(vec![snake(0, 1, &[(5, 5), (5, 4), (5, 3)])], &[]);
+        let (next, deaths) = step(&game, &[Direction::Up]);
+        assert!(next.snakes.is_empty());
+        assert!(matches!(deaths.get(&0), Some(DeathKind::Normal)));
+    }
+
+    #[test]
+    fn eating_on_the_last_turn_of_health_saves_you() {
+        let game =
+            game(vec![snake(0, 1, &[(5, 5), (5, 4), (5, 3)])], &[(5, 6)]);
+        let (next, deaths) = step(&game, &[Direction::Up]);
+        assert!(deaths.is_empty());
+        assert_eq!(next.snakes[0].health, MAX_HEALTH);
+        assert_eq!(next.snakes[0].body.len(), 4);
+        assert!(next.food.is_empty());
+    }
+
+    #[test]
+    fn leaving_the_board_is_fatal() {
+        let game = game(vec![snake(0, 50, &[(0, 5), (1, 5), (2, 5)])], &[]);
+        let (next, deaths) = step(&game, &[Direction::Left]);
+        assert!(next.snakes.is_empty());
+        assert!(matches!(deaths.get(&0), Some(DeathKind::Normal)));
+    }
+
+    #[test]
+    fn wrapped_boards_have_no_edges() {
+        let mut game = game(vec![snake(0, 50, &[(0, 5), (1, 5), (2, 5)])], &[]);
+        game.board.topology = Topology::Wrapped;
+        let (next, deaths) = step(&game, &[Direction::Left]);
+        assert!(deaths.is_empty());
+        assert_eq!(body(&next, 0)[0], Coord { x: 10, y: 5 });
+        assert_eq!(next.snakes[0].facing(&next.board), Some(Direction::Left));
+    }
+
+    #[test]
+    fn running_into_yourself_is_fatal() {
+        let game = game(
+            vec![snake(0, 50, &[(5, 5), (6, 5), (6, 4), (5, 4), (4, 4)])],
+            &[],
+        );
+        let (_, deaths) = step(&game, &[Direction::Down]);
+        assert!(matches!(deaths.get(&0), Some(DeathKind::Normal)));
+    }
+
+    #[test]
+    fn chasing_your_own_tail_is_safe() {
+        let game =
+            game(vec![snake(0, 50, &[(5, 5), (6, 5), (6, 4), (5, 4)])], &[]);
+        let (next, deaths) = step(&game, &[Direction::Down]);
+        assert!(deaths.is_empty());
+        assert_eq!(body(&next, 0)[0], Coord { x: 5, y: 4 });
+    }
+
+    #[test]
+    fn running_into_another_body_is_fatal() {
+        let game = game(
+            vec![
+                snake(0, 50, &[(4, 5), (3, 5), (2, 5)]),
+                snake(1, 50, &[(5, 6), (5, 5), (5, 4), (5, 3)]),
+            ],
+            &[],
+        );
+        let (next, deaths) = step(&game, &[Direction::Right, Direction::Up]);
+        assert!(matches!(deaths.get(&0), Some(DeathKind::Normal)));
+        assert!(!deaths.contains_key(&1));
+        assert_eq!(next.snakes.len(), 1);
+    }
+
+    #[test]
+    fn a_tail_that_just_grew_does_not_move() {
+        let game = game(
+            vec![
+                snake(0, 50, &[(3, 4), (2, 4), (1, 4)]),
+                snake(1, 50, &[(5, 6), (5, 5), (5, 4), (5, 3)]),
+            ],
+            &[(5, 7)],
+        );
+        let (next, deaths) = step(&game, &[Direction::Right, Direction::Up]);
+        assert!(deaths.is_empty());
+        assert_eq!(body(&next, 1).len(), 5);
+
+        let (_, deaths) = step(&next, &[Direction::Right, Direction::Up]);
+        assert!(matches!(deaths.get(&0), Some(DeathKind::Normal)));
+        assert!(!deaths.contains_key(&1));
+    }
+
+    #[test]
+    fn starved_snakes_are_not_obstacles() {
+        let game = game(
+            vec![
+                snake(0, 50, &[(4, 5), (3, 5), (2, 5)]),
+                snake(1, 1, &[(5, 6), (5, 5), (5, 4), (5, 3)]),
+            ],
+            &[],
+        );
+        let (next, deaths) = step(&game, &[Direction::Right, Direction::Up]);
+        assert!(!deaths.contains_key(&0));
+        assert!(matches!(deaths.get(&1), Some(DeathKind::Normal)));
+        assert_eq!(next.snakes.len(), 1);
+    }
+
+    #[test]
+    fn snakes_can_eliminate_each_other() {
+        // each snake's head lands on the other's neck.
+        let game = game(
+            vec![
+                snake(0, 50, &[(5, 5), (5, 4), (5, 3)]),
+                snake(1, 50, &[(6, 6), (5, 6), (4, 6)]),
+            ],
+            &[],
+        );
+        let (next, deaths) = step(&game, &[Direction::Up, Direction::Left]);
+        assert_eq!(body(&game, 1)[1], Coord { x: 5, y: 6 });
+        assert!(matches!(deaths.get(&0), Some(DeathKind::Normal)));
+        assert!(matches!(deaths.get(&1), Some(DeathKind::Normal)));
+        assert!(next.snakes.is_empty());
+    }
+
+    #[test]
+    fn the_shorter_snake_loses_a_head_to_head() {
+        let game = game(
+            vec![
+                snake(0, 50, &[(4, 5), (3, 5), (2, 5)]),
+                snake(1, 50, &[(6, 5), (7, 5), (8, 5), (9, 5)]),
+            ],
+            &[],
+        );
+        let (next, deaths) = step(&game, &[Direction::Right, Direction::Left]);
+        assert!(matches!(deaths.get(&0), Some(DeathKind::Honourable)));
+        assert!(!deaths.contains_key(&1));
+        assert_eq!(next.snakes.len(), 1);
+    }
+
+    #[test]
+    fn equal_snakes_both_lose_a_head_to_head() {
+        let game = game(
+            vec![
+                snake(0, 50, &[(4, 5), (3, 5), (2, 5)]),
+                snake(1, 50, &[(6, 5), (7, 5), (8, 5)]),
+            ],
+            &[],
+        );
+        let (next, deaths) = step(&game, &[Direction::Right, Direction::Left]);
+        assert!(matches!(deaths.get(&0), Some(DeathKind::Honourable)));
+        assert!(matches!(deaths.get(&1), Some(DeathKind::Honourable)));
+        assert!(next.snakes.is_empty());
+    }
+
+    #[test]
+    fn food_is_eaten_before_head_to_heads() {
+        // both snakes reach the food, which makes the eater longer.
+        let game = game(
+            vec![
+                snake(0, 50, &[(4, 5), (3, 5), (2, 5)]),
+                snake(1, 50, &[(5, 6), (5, 7), (5, 8)]),
+            ],
+            &[(5, 5)],
+        );
+        let (next, deaths) = step(&game, &[Direction::Right, Direction::Down]);
+        assert!(matches!(deaths.get(&0), Some(DeathKind::Honourable)));
+        assert!(matches!(deaths.get(&1), Some(DeathKind::Honourable)));
+        assert!(next.food.is_empty());
+    }
+
+    #[test]
+    fn hazards_hurt_unless_you_eat() {
+        let mut game = game(
+            vec![
+                snake(0, 50, &[(4, 5), (3, 5), (2, 5)]),
+                snake(1, 50, &[(4, 8), (3, 8), (2, 8)]),
+            ],
+            &[(5, 8)],
+        );
+        game.hazards = vec![Coord { x: 5, y: 5 }, Coord { x: 5, y: 8 }];
+        let (next, deaths) = step(&game, &[Direction::Right, Direction::Right]);
+        assert!(deaths.is_empty());
+        assert_eq!(next.snakes[0].health, 49 - game.rules.hazard_damage);
+        assert_eq!(next.snakes[1].health, MAX_HEALTH);
+    }
+
+    #[test]
+    fn constrictor_snakes_always_grow() {
+        let mut game = game(vec![snake(0, 50, &[(5, 5), (5, 4), (5, 3)])], &[]);
+        game.rules.constrictor = true;
+        game.rules.minimum_food = 1;
+        let (next, _) = step(&game, &[Direction::Up]);
+        let (next, _) = step(&next, &[Direction::Up]);
+        assert_eq!(next.snakes[0].health, MAX_HEALTH);
+        assert_eq!(body(&next, 0).len(), 5);
+        assert_eq!(body(&next, 0).back(), Some(&Coord { x: 5, y: 4 }));
+        assert!(next.food.is_empty());
+    }
+
+    #[test]
+    fn constrictor_boards_are_cleared_of_food() {
+        let mut game = game(
+            vec![snake(0, 50, &[(5, 5), (5, 4), (5, 3)])],
+            &[(5, 6), (1, 1)],
+        );
+        game.rules.constrictor = true;
+        let (next, _) = step(&game, &[Direction::Up]);
+        assert!(next.food.is_empty());
+        // the food we landed on still counts before it's cleared away.
+        assert_eq!(body(&next, 0).len(), 5);
+    }
+
+    fn squad_game(snakes: Vec<(Snake, SquadID)>) -> Game {
+        let mut game = game(
+            snakes
+                .into_iter()
+                .map(|(mut snake, squad)| {
+                    snake.squad = Some(squad);
+                    snake
+                })
+                .collect(),
+            &[],
+        );
+        game.rules.squad = Some(SquadRules::default());
+        game
+    }
+
+    #[test]
+    fn team_mates_pass_through_each_other() {
+        let game = squad_game(vec![
+            (snake(0, 50, &[(4, 5), (3, 5), (2, 5)]), 0),
+            (snake(1, 50, &[(5, 6), (5, 5), (5, 4), (5, 3)]), 0),
+            (snake(2, 50, &[(9, 9), (9, 8), (9, 7)]), 1),
+        ]);
+        let (next, deaths) =
+            step(&game, &[Direction::Right, Direction::Up, Direction::Up]);
+        assert!(deaths.is_empty());
+        assert_eq!(body(&next, 0).len(), 4);
+        assert!(!next.is_over());
+    }
+
+    #[test]
+    fn team_mates_fall_together() {
+        let game = squad_game(vec![
+            (snake(0, 50, &[(0, 5), (1, 5), (2, 5)]), 0),
+            (snake(1, 50, &[(5, 6), (5, 5), (5, 4)]), 0),
+            (snake(2, 50, &[(9, 9), (9, 8), (9, 7)]), 1),
+            (snake(3, 50, &[(7, 7), (7, 8), (7, 9)]), 1),
+        ]);
+        let (next, deaths) = step(
+            &game,
+            &[
+                Direction::Left,
+                Direction::Up,
+                Direction::Up,
+                Direction::Down,
+            ],
+        );
+        assert!(deaths.contains_key(&0));
+        assert!(deaths.contains_key(&1));
+        assert_eq!(next.snakes.len(), 2);
+        assert!(next.is_over());
+    }
+
+    #[test]
+    fn food_spawns_up_to_the_minimum_on_free_squares() {
+        let mut game = game(vec![snake(0, 50, &[(5, 5), (5, 4), (5, 3)])], &[]);
+        game.rules.minimum_food = 3;
+        let (next, _) = step(&game, &[Direction::Up]);
+        assert_eq!(next.food.len(), 3);
+        for food in &next.food {
+            assert!(!next.snakes[0].body.contains(food));
+            assert!(Direction::iter()
+                .all(|d| next.board.neighbour(next.snakes[0].body[0], *d)
+                    != *food));
+        }
+    }
+
+    #[test]
+    fn food_spawns_where_the_fallen_just_were() {
+        let mut game = game(
+            vec![
+                snake(0, 50, &[(5, 5), (5, 4), (5, 3)]),
+                snake(1, 50, &[(0, 10), (1, 10), (2, 10)]),
+            ],
+            &[],
+        );
+        game.rules.minimum_food = 1;
+        // hazards everywhere except under snake 1's head, and where snake 0
+        // is going.
+        game.hazards = (0..11)
+            .flat_map(|x| (0..11).map(move |y| Coord { x, y }))
+            .filter(|&c| {
+                c != Coord { x: 0, y: 10 } && c != Coord { x: 5, y: 6 }
+            })
+            .collect();
+
+        let (next, deaths) = step(&game, &[Direction::Up, Direction::Up]);
+        assert!(deaths.contains_key(&1));
+        assert_eq!(next.food, vec![Coord { x: 0, y: 10 }]);
+    }
+
+    /// The key `game` would have if it were set up from scratch.
+    fn fresh_key(game: &Game) -> Key {
+        Game::new(
+            game.snakes
+                .iter()
+                .map(|s| Snake::new(s.id, s.body.clone(), s.health, s.squad))
+                .collect(),
+            game.food.clone(),
+            game.hazards.clone(),
+            game.board.clone(),
+            game.rules.clone(),
+        )
+        .key()
+    }
+
+    fn walk(game: &Game, moves: &[Direction]) -> Game {
+        moves.iter().fold(game.clone(), |game, &direction| {
+            let moves = HashMap::from([(0, direction)]);
+            game.step(&moves).expect("step failed").0
+        })
+    }
+
+    #[test]
+    fn bodies_wall_off_space() {
+        let wall: Vec<(i64, i64)> =
+            iter::once((0, 0)).chain((0..11).map(|y| (1, y))).collect();
+        let game = game(vec![snake(0, MAX_HEALTH, &wall)], &[]);
+        assert_eq!(reachable(&game, MAX_HEALTH), 11);
+    }
+
+    #[test]
+    fn health_limits_how_far_you_can_reach() {
+        let mut game = game(vec![snake(0, 3, &[(0, 0), (0, 0)])], &[]);
+        assert_eq!(reachable(&game, 3), 6);
+
+        game.hazards = vec![Coord { x: 1, y: 0 }, Coord { x: 0, y: 1 }];
+        assert_eq!(reachable(&game, 3), 1);
+
+        // food cancels out the hazard under it and fills us back up.
+        game.food = vec![Coord { x: 1, y: 0 }];
+        assert_eq!(reachable(&game, 3), 121);
+    }
+
+    #[test]
+    fn territory_goes_to_whoever_gets_there_first() {
+        let mut game = game(
+            vec![
+                snake(0, 50, &[(2, 5), (1, 5), (0, 5)]),
+                snake(1, 50, &[(8, 5), (9, 5), (10, 5)]),
+            ],
+            &[],
+        );
+        let territories = |game: &Game| -> Vec<(i64, Option<i64>)> {
+            game.territories(&game.terrain::<u128>(&Undo::default()))
+                .iter()
+                .take(game.snakes.len())
+                .map(|territory| (territory.squares, territory.nearest_food))
+                .collect()
+        };
+
+        // the middle column is a tie, so nobody gets it, or the food in it.
+        game.food = vec![Coord { x: 5, y: 7 }, Coord { x: 1, y: 4 }];
+        assert_eq!(territories(&game), vec![(53, Some(2)), (53, None)]);
+
+        // unless one of us is longer.
+        game.snakes[1].grow();
+        assert_eq!(territories(&game), vec![(53, Some(2)), (64, Some(5))]);
+    }
+
+    #[test]
+    fn big_boards_are_simulated_too() {
+        let mut game = game(
+            vec![
+                snake(0, 50, &[(15, 15), (15, 14), (15, 13)]),
+                snake(1, 50, &[(14, 16), (15, 16), (16, 16)]),
+            ],
+            &[],
+        );
+        game.board.width = 19;
+        game.board.height = 19;
+
+        let moves = [(0, Direction::Up), (1, Direction::Left)].into();
+        let (next, undo) = game.step(&moves).expect("step failed");
+        assert!(next.snakes.iter().all(|snake| snake.id == 1));
+
+        let scores = next.scores(&undo).expect("scoring failed");
+        assert_eq!(scores.len(), 2);
+    }
+
+    #[test]
+    fn huge_boards_are_simulated_too() {
+        let mut game = game(
+            vec![
+                snake(0, 50, &[(38, 38), (38, 37), (38, 36)]),
+                snake(1, 50, &[(37, 39), (38, 39), (39, 39)]),
+            ],
+            &[],
+        );
+        game.board.width = 40;
+        game.board.height = 40;
+
+        let moves = [(0, Direction::Up), (1, Direction::Left)].into();
+        let (next, undo) = game.step(&moves).expect("step failed");
+        assert!(next.snakes.iter().all(|snake| snake.id == 1));
+
+        let scores = next.scores(&undo).expect("scoring failed");
+        assert!(scores[0].dead);
+        assert!(scores[1].available_squares > 0);
+        assert_eq!(
+            next.snakes[0].possible_directions(&next.board),
+            vec![Direction::Left, Direction::Down]
+        );
+    }
+
+    #[test]
+    fn anyone_can_be_put_first() {
+        let game = game(
+            vec![
+                snake(0, 90, &[(1, 1), (1, 2)]),
+                snake(1, 80, &[(5, 5), (5, 6)]),
+                snake(2, 70, &[(9, 9), (9, 8)]),
+            ],
+            &[(3, 3)],
+        );
+
+        let seen = game.seen_by(2).expect("snake #2 is playing");
+        let healths: Vec<_> =
+            seen.snakes.iter().map(|s| (s.id, s.health)).collect();
+        assert_eq!(healths, vec![(0, 70), (1, 80), (2, 90)]);
+        assert_eq!(seen.food, game.food);
+        assert_eq!(
+            seen.seen_by(2).expect("snake #2 is still playing").key(),
+            game.key()
+        );
+
+        assert!(game.seen_by(3).is_none());
+    }
+
+    #[test]
+    fn keys_keep_up_with_the_game() {
+        let base = game(
+            vec![
+                snake(0, 50, &[(4, 5), (3, 5), (2, 5)]),
+                snake(1, 50, &[(4, 8), (3, 8), (2, 8)]),
+            ],
+            &[(5, 8), (6, 5)],
+        );
+        let mut game = Game::new(
+            base.snakes
+                .into_iter()
+                .map(|mut snake| {
+                    snake.squad = Some(0);
+                    snake
+                })
+                .collect(),
+            base.food,
+            vec![Coord { x: 5, y: 5 }],
+            base.board,
+            Rules {
+                squad: Some(SquadRules::default()),
+                ..base.rules
+            },
+        );
+
+        for _ in 0..3 {
+            let moves =
+                HashMap::from([(0, Direction::Right), (1, Direction::Right)]);
+            game = game.step(&moves).expect("step failed").0;
+            assert_eq!(game.key(), fresh_key(&game));
+        }
+
+        game.rules.constrictor = true;
+        let (next, _) = step(&game, &[Direction::Up, Direction::Down]);
+        assert_eq!(next.key(), fresh_key(&next));
+    }
+
+    /// Everything about a game that a turn can change.
+    type Snapshot =
+        (Vec<(SnakeID, VecDeque<Coord>, i64, Key)>, Vec<Coord>, Key);
+
+    fn snapshot(game: &Game) -> Snapshot {
+        (
+            game.snakes
+                .iter()
+                .map(|s| (s.id, s.body.clone(), s.health, s.key()))
+                .collect(),
+            game.food.clone(),
+            game.board_key,
+        )
+    }
+
+    #[test]
+    fn unmaking_turns_puts_everything_back() {
+        // snake 0 loses a head-to-head and takes its team-mate down with it,
+        // while snake 3 eats and shares its health with snake 1.
+        let squads = squad_game(vec![
+            (snake(0, 50, &[(4, 5), (3, 5), (2, 5)]), 0),
+            (snake(1, 50, &[(6, 5), (7, 5), (8, 5), (9, 5)]), 1),
+            (snake(2, 50, &[(1, 1), (1, 0), (2, 0)]), 0),
+            (snake(3, 50, &[(8, 8), (8, 7), (8, 6)]), 1),
+        ]);
+        let mut game = Game::new(
+            squads.snakes,
+            vec![Coord { x: 0, y: 10 }, Coord { x: 8, y: 9 }],
+            vec![],
+            squads.board,
+            squads.rules,
+        );
+        let before = snapshot(&game);
+
+        let mut first = Undo::default();
+        let moves = HashMap::from([
+            (0, Direction::Right),
+            (1, Direction::Left),
+            (2, Direction::Up),
+            (3, Direction::Up),
+        ]);
+        game.make(&moves, &mut first).expect("make failed");
+        assert!(matches!(
+            first.death_kinds.get(&0),
+            Some(DeathKind::Honourable)
+        ));
+        assert!(matches!(first.death_kinds.get(&2), Some(DeathKind::Normal)));
+        assert_eq!(game.snakes[0].health, MAX_HEALTH);
+        assert_eq!(game.food, vec![Coord { x: 0, y: 10 }]);
+        let middle = snapshot(&game);
+
+        let mut second = Undo::default();
+        let moves = HashMap::from([(1, Direction::Left), (3, Direction::Up)]);
+        game.make(&moves, &mut second).expect("make failed");
+
+        game.unmake(&mut second);
+        assert_eq!(snapshot(&game), middle);
+        game.unmake(&mut first);
+        assert_eq!(snapshot(&game), before);
+    }
+
+    #[test]
+    fn transpositions_share_a_key() {
+        let game = game(vec![snake(0, 50, &[(5, 5), (5, 4), (5, 3)])], &[]);
+        let a = walk(
+            &game,
+            &[
+                Direction::Up,
+                Direction::Right,
+                Direction::Up,
+                Direction::Up,
+            ],
+        );
+        let b = walk(
+            &game,
+            &[
+                Direction::Right,
+                Direction::Up,
+                Direction::Up,
+                Direction::Up,
+            ],
+        );
+        let c = walk(
+            &game,
+            &[
+                Direction::Up,
+                Direction::Up,
+                Direction::Right,
+                Direction::Up,
+            ],
+        );
+        assert_eq!(a.key(), b.key());
+        assert_ne!(a.key(), c.key());
+    }
+}
@@ -0,0 +1,219 @@
+use std::cmp::Ordering;
+
+/// The outcome of a two-player zero-sum matrix game, from the point of view of
+/// the row player.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Solution {
+    /// the row the row player should pick.
+    pub row:    usize,
+    /// the column the column player picks in response.
+    pub column: usize,
+    /// the payoff the row player can expect.
+    pub value:  i64,
+}
+
+/// Picks the row with the best worst case, as if the column player could see
+/// our choice before making theirs.
+///
+/// `payoffs` must have at least one row, and every row must have the same
+/// number of columns (at least one).
+pub fn max_min(payoffs: &[Vec<i64>]) -> Solution {
+    let mut best = Solution {
+        row:    0,
+        column: 0,
+        value:  i64::MIN,
+    };
+
+    for (row, cells) in payoffs.iter().enumerate() {
+        let (column, &value) = cells
+            .iter()
+            .enumerate()
+            .min_by_key(|&(_, &value)| value)
+            .unwrap_or((0, &i64::MIN));
+        if row == 0 || value > best.value {
+            best = Solution { row, column, value };
+        }
+    }
+
+    best
+}
+
+/// Finds the mixed strategy that maximises the row player's expected payoff,
+/// no matter what the column player does, and picks its most likely row.
+///
+/// Only that one row ever gets played, so the mix as a whole can't be relied
+/// on. The column and `value` are the column player's best response to the row
+/// and what it leaves us with, the same as [`max_min`] would say about it.
+///
+/// `payoffs` has the same requirements as for [`max_min`].
+#[allow(clippy::cast_precision_loss)]
+pub fn mixed(payoffs: &[Vec<i64>]) -> Solution {
+    let pure = max_min(payoffs);
+
+    let lowest = payoffs.iter().flatten().copied().min().unwrap_or(0);
+    let highest = payoffs.iter().flatten().copied().max().unwrap_or(0);
+    if lowest == highest {
+        return pure;
+    }
+
+    // squash everything into [1, 2]. this keeps the floats well conditioned,
+    // and a strictly positive game is what the linear program below needs.
+    let span = (highest - lowest) as f64;
+    let scaled: Vec<Vec<f64>> = payoffs
+        .iter()
+        .map(|cells| {
+            cells
+                .iter()
+                .map(|&value| 1.0 + (value - lowest) as f64 / span)
+                .collect()
+        })
+        .collect();
+
+    let strategy = row_strategy(&scaled);
+
+    let Some(row) = strategy
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(row, _)| row)
+    else {
+        return pure;
+    };
+
+    let Some((column, &value)) = payoffs[row]
+        .iter()
+        .enumerate()
+        .min_by_key(|&(_, &value)| value)
+    else {
+        return pure;
+    };
+
+    Solution { row, column, value }
+}
+
+/// Solves the column player's side of the game as a linear program with the
+/// simplex method, and reads the row player's optimal strategy off the dual.
+///
+/// Maximise `sum(q)` subject to `A q <= 1, q >= 0`. The slack variables start
+/// out as a feasible basis, so no first phase is needed.
+fn row_strategy(payoffs: &[Vec<f64>]) -> Vec<f64> {
+    const EPSILON: f64 = 1e-9;
+
+    let rows = payoffs.len();
+    let columns = payoffs[0].len();
+    let width = columns + rows + 1;
+    let objective = rows;
+    let rhs = width - 1;
+
+    let mut tableau = vec![vec![0.0; width]; rows + 1];
+    for (row, cells) in payoffs.iter().enumerate() {
+        tableau[row][..columns].copy_from_slice(cells);
+        tableau[row][columns + row] = 1.0;
+        tableau[row][rhs] = 1.0;
+    }
+    for cell in &mut tableau[objective][..columns] {
+        *cell = -1.0;
+    }
+    let mut basis: Vec<usize> = (columns..columns + rows).collect();
+
+    // bland's rule: the lowest index always enters and leaves, so the
+    // method can't cycle on degenerate games.
+    while let Some(entering) =
+        (0..rhs).find(|&column| tableau[objective][column] < -EPSILON)
+    {
+        let Some(leaving) = (0..rows)
+            .filter(|&row| tableau[row][entering] > EPSILON)
+            .min_by(|&a, &b| {
+                let ratio_a = tableau[a][rhs] / tableau[a][entering];
+                let ratio_b = tableau[b][rhs] / tableau[b][entering];
+                match ratio_a.total_cmp(&ratio_b) {
+                    Ordering::Equal => basis[a].cmp(&basis[b]),
+                    ordering => ordering,
+                }
+            })
+        else {
+            // unbounded, which a strictly positive game can't be.
+            break;
+        };
+
+        let pivot = tableau[leaving][entering];
+        for cell in &mut tableau[leaving] {
+            *cell /= pivot;
+        }
+        let pivot_row = tableau[leaving].clone();
+        for (row, cells) in tableau.iter_mut().enumerate() {
+            let factor = cells[entering];
+            if row == leaving || factor == 0.0 {
+                continue;
+            }
+            for (cell, pivot_cell) in cells.iter_mut().zip(&pivot_row) {
+                *cell -= factor * pivot_cell;
+            }
+        }
+        basis[leaving] = entering;
+    }
+
+    // the objective is 1 / value, and the slack prices are the row player's
+    // strategy scaled by the same amount.
+    let total = tableau[objective][rhs];
+    (0..rows)
+        .map(|row| tableau[objective][columns + row] / total)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_min_assumes_the_worst() {
+        let payoffs = vec![vec![1, -1], vec![-1, 1]];
+        assert_eq!(max_min(&payoffs).value, -1);
+    }
+
+    #[test]
+    fn saddle_points_are_pure() {
+        let payoffs = vec![vec![3, 1], vec![4, 2]];
+        let expected = Solution {
+            row:    1,
+            column: 1,
+            value:  2,
+        };
+        assert_eq!(max_min(&payoffs), expected);
+        assert_eq!(mixed(&payoffs), expected);
+    }
+
+    #[test]
+    fn matching_pennies_can_be_punished() {
+        // the mix is worth 0 on average, but whichever row we actually play
+        // can be answered with -1.
+        let payoffs = vec![vec![1, -1], vec![-1, 1]];
+        let solution = mixed(&payoffs);
+        assert_eq!(solution.value, -1);
+        assert_eq!(payoffs[solution.row][solution.column], -1);
+    }
+
+    #[test]
+    fn rock_paper_scissors_can_be_punished() {
+        let payoffs = vec![vec![0, -1, 1], vec![1, 0, -1], vec![-1, 1, 0]];
+        assert_eq!(mixed(&payoffs).value, -1);
+    }
+
+    #[test]
+    fn mixing_avoids_rows_nobody_should_play() {
+        // an even mix of the first two rows guarantees 50 on average. the
+        // third row is never part of it, even though its worst case is
+        // better than theirs.
+        let payoffs = vec![vec![100, -20], vec![-20, 100], vec![-10, -10]];
+        let solution = mixed(&payoffs);
+        assert_eq!(max_min(&payoffs).row, 2);
+        assert_ne!(solution.row, 2);
+        assert_eq!(solution.value, -20);
+    }
+
+    #[test]
+    fn constant_games_are_trivial() {
+        let payoffs = vec![vec![7, 7], vec![7, 7]];
+        assert_eq!(mixed(&payoffs).value, 7);
+    }
+}
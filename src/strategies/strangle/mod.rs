@@ -1,82 +1,358 @@
+mod bitboard;
 mod board;
 pub mod brain;
-mod game;
-mod score_factors;
+pub mod game;
+mod matrix;
+mod rules;
+pub mod score_factors;
 mod snake;
+pub mod transposition;
 mod utils;
+mod zobrist;
 
 use std::{
     collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+        Mutex,
+        PoisonError,
+    },
+    thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
 
 use color_eyre::{eyre::eyre, Result};
+use log::debug;
 
 use self::game::Game;
-use super::Strategy;
+use super::{time_manager::TimeManager, Decision, Strategy};
 use crate::{
     fightsnake::{models::GameState, types::Direction},
-    strategies::strangle::brain::{bigbrain, BigbrainOptions, BigbrainResult},
+    strategies::strangle::{
+        brain::{
+            deepen,
+            describe_choices,
+            describe_variation,
+            ponder,
+            Algorithm,
+            BigbrainOptions,
+            BigbrainResult,
+        },
+        game::Type,
+        score_factors::Weights,
+        transposition::TranspositionTable,
+    },
 };
 
 pub const TRACE_SIM: bool = false;
 
-pub struct Strangle;
+pub struct Strangle {
+    time_manager: TimeManager,
+    /// each game's transposition table, kept between turns.
+    caches:       Mutex<HashMap<String, GameCache>>,
+    /// whether to keep searching in the background between turns.
+    pondering:    bool,
+    /// how many threads each search is split between.
+    threads:      usize,
+    weights:      Weights,
+    /// how deep to search, if it should always be the same depth however long
+    /// that takes.
+    max_depth:    Option<u64>,
+}
 
-type SnakeID = usize;
-const ME: SnakeID = 0;
+struct GameCache {
+    table:     Table,
+    last_used: Instant,
+}
 
-impl Strategy for Strangle {
-    fn get_movement(&self, game_state: GameState) -> Result<Direction> {
-        const TIME_LIMIT: Duration = Duration::from_millis(400);
+/// Where a game's transposition table is between turns.
+enum Table {
+    /// waiting for the next turn.
+    Idle(TranspositionTable),
+    /// lent out to a thread that's pondering the next turn.
+    Pondering {
+        stop:   Arc<AtomicBool>,
+        handle: JoinHandle<TranspositionTable>,
+    },
+}
+
+impl Table {
+    /// Gets the table back, stopping any pondering first.
+    fn finish(self) -> TranspositionTable {
+        match self {
+            Self::Idle(table) => table,
+            Self::Pondering { stop, handle } => {
+                stop.store(true, Ordering::Relaxed);
+                // a thread that panicked takes its table down with it.
+                handle.join().unwrap_or_default()
+            },
+        }
+    }
+
+    /// Tells any pondering to give up, without waiting for it to do so.
+    fn abandon(&self) {
+        if let Self::Pondering { stop, .. } = self {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+pub type SnakeID = usize;
+type SquadID = usize;
+pub const ME: SnakeID = 0;
+
+/// The id the engine gave each snake in `game_state`, indexed by the
+/// [`SnakeID`] it gets once the game is turned into a [`Game`]. That puts us
+/// first, so whoever was first takes our place.
+#[must_use]
+pub fn snake_ids(game_state: &GameState) -> Vec<String> {
+    let mut ids: Vec<String> = game_state
+        .board
+        .snakes
+        .iter()
+        .map(|snake| snake.id.clone())
+        .collect();
+    if let Some(you) = ids.iter().position(|id| *id == game_state.you.id) {
+        ids.swap(ME, you);
+    }
+    ids
+}
+
+impl Strangle {
+    /// time left spare for the network if we haven't measured anything worse.
+    pub const DEFAULT_NETWORK_MARGIN: Duration = Duration::from_millis(100);
+    /// games we haven't heard from in this long are assumed to be over, even
+    /// if we never got told about it.
+    const INACTIVE_AFTER: Duration = Duration::from_mins(1);
+
+    #[must_use]
+    pub fn new(network_margin: Duration) -> Self {
+        Self {
+            time_manager: TimeManager::new(network_margin),
+            caches:       Mutex::new(HashMap::new()),
+            pondering:    true,
+            threads:      1,
+            weights:      Weights::DEFAULT,
+            max_depth:    None,
+        }
+    }
+
+    /// Stops us thinking between turns, which is only polite when sharing the
+    /// machine with other snakes.
+    #[must_use]
+    pub const fn without_pondering(mut self) -> Self {
+        self.pondering = false;
+        self
+    }
+
+    /// Splits each search between `threads` threads, so we get deeper in the
+    /// same amount of time on a machine with cores to spare.
+    #[must_use]
+    pub const fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Judges positions by `weights` instead of the defaults.
+    #[must_use]
+    pub const fn with_weights(mut self, weights: Weights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Always searches exactly `depth` turns ahead instead of as far as time
+    /// allows, so the same game always gets the same move.
+    #[must_use]
+    pub const fn with_max_depth(mut self, depth: u64) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
 
+    /// Searches for our move in `game_state`, and gives back everything the
+    /// search found out along the way. This is what picks our move in
+    /// [`Strategy::get_movement`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the game state doesn't make sense, for example if we aren't in
+    /// it.
+    pub fn think(&self, game_state: GameState) -> Result<BigbrainResult> {
         let start = Instant::now();
 
+        let time_limit = if self.max_depth.is_some() {
+            Duration::MAX
+        } else {
+            self.time_manager.budget(&game_state)
+        };
+        let game_id = game_state.game.id.clone();
+        let turn = game_state.turn;
+
         let game = Game::try_from(game_state)?;
+        let known_scores = self.take_table(&game_id);
+
+        // pruning needs exactly one opponent to work against.
+        let algorithm = match game.game_type() {
+            Type::Duel => Algorithm::AlphaBeta,
+            _ => Algorithm::MaxN,
+        };
+
+        let (result, nodes) = deepen(
+            &game,
+            &BigbrainOptions {
+                max_depth: self.max_depth.unwrap_or(u64::MAX),
+                time_limit,
+                algorithm,
+                threads: self.threads,
+                weights: self.weights,
+            },
+            &known_scores,
+            start,
+        )?;
+
+        println!(
+            "got a result from depth {} ({nodes} nodes, {algorithm:?}, {}/{} \
+             transposition hits) in {:?} of {}",
+            result.depth,
+            known_scores.hits(),
+            known_scores.probes(),
+            start.elapsed(),
+            self.max_depth.map_or_else(
+                || format!("{time_limit:?}"),
+                |depth| format!("depth {depth}")
+            )
+        );
+        debug!("choices: {}", describe_choices(&result.choices));
+        debug!(
+            "expecting:\n{}",
+            describe_variation(&result.variation, turn, |snake_id| {
+                format!("#{snake_id}")
+            })
+        );
+
+        self.time_manager.finish(&game_id, start.elapsed());
 
-        let mut depth = 1;
+        let direction = result.direction.ok_or(eyre!(
+            "bigbrain must return a direction from the root invocation"
+        ))?;
+        self.return_table(game_id, game, direction, algorithm, known_scores);
+
+        Ok(result)
+    }
 
-        let mut result = BigbrainResult {
-            scores:    HashMap::new(),
-            direction: None,
-            depth:     0,
+    /// Takes the game's transposition table out of the cache, or makes a new
+    /// one if this is the first we've seen of the game. Tables for games that
+    /// have gone quiet are thrown away while we're at it.
+    fn take_table(&self, game_id: &str) -> TranspositionTable {
+        let table = {
+            let mut caches =
+                self.caches.lock().unwrap_or_else(PoisonError::into_inner);
+            caches.retain(|_, cache| {
+                let active = cache.last_used.elapsed() < Self::INACTIVE_AFTER;
+                if !active {
+                    cache.table.abandon();
+                }
+                active
+            });
+            caches.remove(game_id)
         };
 
-        let mut known_scores = HashMap::new();
-
-        while start.elapsed() < TIME_LIMIT {
-            match bigbrain(
-                &game,
-                0,
-                0,
-                &HashMap::new(),
-                &mut known_scores,
-                start,
-                &BigbrainOptions {
-                    max_depth:  depth,
-                    time_limit: TIME_LIMIT,
-                },
-            )? {
-                Some(new_result) => {
-                    result = new_result;
-                    if result.depth < depth {
-                        println!(
-                            "bigbrain only got to depth {}/{}, exiting early.",
-                            result.depth, depth
-                        );
-                        break;
+        // waiting for the ponderer to stop has to happen outside of the lock.
+        table.map_or_else(TranspositionTable::default, |c| c.table.finish())
+    }
+
+    /// Puts a game's transposition table back. If we're pondering, the table
+    /// is used to keep searching the positions we might see next turn until
+    /// the next turn actually comes.
+    fn return_table(
+        &self,
+        game_id: String,
+        game: Game,
+        direction: Direction,
+        algorithm: Algorithm,
+        table: TranspositionTable,
+    ) {
+        let table = if self.pondering {
+            let weights = self.weights;
+            let stop = Arc::new(AtomicBool::new(false));
+            let handle = thread::spawn({
+                let stop = Arc::clone(&stop);
+                move || {
+                    if let Err(e) = ponder(
+                        &game,
+                        direction,
+                        algorithm,
+                        weights,
+                        &table,
+                        &stop,
+                        Self::INACTIVE_AFTER,
+                    ) {
+                        println!("pondering failed: {e}");
                     }
+                    table
+                }
+            });
+            Table::Pondering { stop, handle }
+        } else {
+            Table::Idle(table)
+        };
+
+        self.caches
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                game_id,
+                GameCache {
+                    table,
+                    last_used: Instant::now(),
                 },
-                None => break,
-            }
+            );
+    }
+}
 
-            depth += 1;
-        }
+impl Default for Strangle {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_NETWORK_MARGIN)
+    }
+}
+
+impl Strategy for Strangle {
+    fn get_movement(&self, game_state: GameState) -> Result<Direction> {
+        self.decide(game_state).map(|decision| decision.movement)
+    }
 
-        println!("got a result from depth {depth}");
+    fn decide(&self, game_state: GameState) -> Result<Decision> {
+        let ids = snake_ids(&game_state);
+        let result = self.think(game_state)?;
+        let direction = result.direction.ok_or_else(|| {
+            eyre!("bigbrain must return a direction from the root invocation")
+        })?;
 
-        result.direction.ok_or(eyre!(
-            "bigbrain must return a direction from the root invocation"
-        ))
+        Ok(Decision {
+            movement: direction,
+            depth:    Some(result.depth),
+            scores:   result
+                .scores
+                .iter()
+                .filter_map(|score| {
+                    Some((
+                        ids.get(score.snake_id)?.clone(),
+                        score.calculate(result.depth, &self.weights),
+                    ))
+                })
+                .collect(),
+        })
+    }
+
+    fn end(&self, game_state: &GameState) {
+        self.time_manager.end(&game_state.game.id);
+        let cache = self
+            .caches
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&game_state.game.id);
+        if let Some(cache) = cache {
+            cache.table.abandon();
+        }
     }
 }
@@ -0,0 +1,105 @@
+use crate::fightsnake::{
+    constants::MAX_HEALTH,
+    models::{Ruleset, SquadSettings},
+};
+
+/// The parts of a game's ruleset that change how a turn plays out.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Rules {
+    pub food_spawn_chance: u64,
+    pub minimum_food:      u64,
+    pub hazard_damage:     i64,
+    /// snakes grow every turn, never go hungry, and there is no food.
+    pub constrictor:       bool,
+    /// how team-mates affect each other, if this is a squad game.
+    pub squad:             Option<SquadRules>,
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)] // these are the engine's own toggles
+pub struct SquadRules {
+    pub allow_body_collisions: bool,
+    pub shared_elimination:    bool,
+    pub shared_health:         bool,
+    pub shared_length:         bool,
+}
+
+impl Default for SquadRules {
+    fn default() -> Self {
+        Self {
+            allow_body_collisions: true,
+            shared_elimination:    true,
+            shared_health:         true,
+            shared_length:         true,
+        }
+    }
+}
+
+impl From<&SquadSettings> for SquadRules {
+    fn from(settings: &SquadSettings) -> Self {
+        let defaults = Self::default();
+        Self {
+            allow_body_collisions: settings
+                .allow_body_collisions
+                .unwrap_or(defaults.allow_body_collisions),
+            shared_elimination:    settings
+                .shared_elimination
+                .unwrap_or(defaults.shared_elimination),
+            shared_health:         settings
+                .shared_health
+                .unwrap_or(defaults.shared_health),
+            shared_length:         settings
+                .shared_length
+                .unwrap_or(defaults.shared_length),
+        }
+    }
+}
+
+impl Rules {
+    const DEFAULT_FOOD_SPAWN_CHANCE: u64 = 15;
+    const DEFAULT_HAZARD_DAMAGE: i64 = 14;
+    const DEFAULT_MINIMUM_FOOD: u64 = 1;
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            food_spawn_chance: Self::DEFAULT_FOOD_SPAWN_CHANCE,
+            minimum_food:      Self::DEFAULT_MINIMUM_FOOD,
+            hazard_damage:     Self::DEFAULT_HAZARD_DAMAGE,
+            constrictor:       false,
+            squad:             None,
+        }
+    }
+}
+
+impl From<&Ruleset> for Rules {
+    fn from(ruleset: &Ruleset) -> Self {
+        let defaults = Self {
+            constrictor: ruleset.name == "constrictor",
+            squad: (ruleset.name == "squad").then(SquadRules::default),
+            ..Self::default()
+        };
+        let Some(settings) = &ruleset.settings else {
+            return defaults;
+        };
+
+        Self {
+            food_spawn_chance: settings
+                .food_spawn_chance
+                .unwrap_or(defaults.food_spawn_chance),
+            minimum_food: settings
+                .minimum_food
+                .unwrap_or(defaults.minimum_food),
+            hazard_damage: settings
+                .hazard_damage_per_turn
+                .map_or(defaults.hazard_damage, |damage| {
+                    i64::try_from(damage).unwrap_or(MAX_HEALTH)
+                }),
+            squad: defaults
+                .squad
+                .map(|squad| settings.squad.as_ref().map_or(squad, Into::into)),
+            ..defaults
+        }
+    }
+}
@@ -1,6 +1,9 @@
-use std::fmt;
+use std::{env, fmt, fs, ops::Index};
 
-use super::SnakeID;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{snake::Snake, SnakeID, SquadID};
 
 #[derive(Debug, Clone, Copy)]
 pub enum DeathKind {
@@ -8,9 +11,99 @@ pub enum DeathKind {
     Honourable,
 }
 
+/// How much each of the score factors counts for.
+///
+/// Any weights left out when deserializing keep their default values, so a
+/// config only has to mention what it changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Weights {
+    pub available_squares:   i64,
+    pub center_dist:         i64,
+    /// how much each turn of staying alive is worth, and each turn of winning
+    /// sooner.
+    pub depth:               i64,
+    /// how much having food right next to us is worth. food further away is
+    /// worth proportionally less.
+    pub food:                i64,
+    pub health:              i64,
+    pub length:              i64,
+    pub remaining_opponents: i64,
+    /// how much worse it is to be unable to get to food before starving.
+    pub starvation:          i64,
+    pub territory:           i64,
+}
+
+impl Weights {
+    pub const DEFAULT: Self = Self {
+        available_squares:   2500,
+        center_dist:         250,
+        depth:               100,
+        food:                2000,
+        health:              200,
+        length:              1500,
+        remaining_opponents: 10_000,
+        starvation:          1_000_000,
+        territory:           1000,
+    };
+
+    /// The weights the snake is set up to play with: read from the file named
+    /// by `WEIGHTS_FILE`, or else from the JSON in `WEIGHTS`, or else the
+    /// defaults. Anything a set of weights leaves out keeps its default.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read, or the weights in it or in `WEIGHTS`
+    /// don't parse.
+    pub fn from_env() -> Result<Self> {
+        if let Ok(path) = env::var("WEIGHTS_FILE") {
+            Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+        } else if let Ok(weights) = env::var("WEIGHTS") {
+            Ok(serde_json::from_str(&weights)?)
+        } else {
+            Ok(Self::DEFAULT)
+        }
+    }
+
+    /// A short name for this set of weights, so we can tell which one a snake
+    /// was playing with.
+    #[must_use]
+    pub fn fingerprint(&self) -> String {
+        if *self == Self::DEFAULT {
+            return "default".to_owned();
+        }
+
+        // FNV-1a, which unlike the standard library's hashers is guaranteed to
+        // give the same answer every time.
+        let hash = serde_json::to_string(self)
+            .unwrap_or_default()
+            .bytes()
+            .fold(0x811C_9DC5_u32, |hash, byte| {
+                (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+            });
+        format!("{hash:08x}")
+    }
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// The part of the board a snake can get to before anyone else.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Territory {
+    pub squares:      i64,
+    /// how many moves it takes to get to the closest food in it, if it has
+    /// any.
+    pub nearest_food: Option<i64>,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct ScoreFactors {
     pub snake_id:            SnakeID,
+    pub squad:               Option<SquadID>,
     pub health:              i64,
     pub length:              i64,
     pub center_dist:         i64,
@@ -18,47 +111,53 @@ pub struct ScoreFactors {
     pub death_kind:          DeathKind,
     pub remaining_opponents: i64,
     pub available_squares:   i64,
+    /// how many squares we can get to before anyone else.
+    pub territory:           i64,
+    /// how many moves it takes us to get to the closest food that nobody can
+    /// get to before us, if there is any.
+    pub nearest_food:        Option<i64>,
     pub multisnake:          bool,
+    /// health and length mean nothing when everyone grows every turn.
+    pub constrictor:         bool,
 }
 
 impl ScoreFactors {
-    const AVAILABLE_SQUARES_WEIGHT: i64 = 2500;
-    const CENTER_DIST_WEIGHT: i64 = 250;
-    const DEPTH_WEIGHT: i64 = 100;
-    const HEALTH_WEIGHT: i64 = 200;
-    const LENGTH_WEIGHT: i64 = 1500;
-    const REMAINING_OPPONENTS_WEIGHT: i64 = 10_000;
-
-    #[allow(clippy::too_many_arguments)]
-    pub const fn alive(
-        snake_id: SnakeID,
-        health: i64,
-        length: i64,
+    #[must_use]
+    pub fn alive(
+        snake: &Snake,
         center_dist: i64,
         remaining_opponents: i64,
         available_squares: i64,
+        territory: Territory,
         multisnake: bool,
+        constrictor: bool,
     ) -> Self {
         Self {
-            snake_id,
-            health,
-            length,
+            snake_id: snake.id,
+            squad: snake.squad,
+            health: snake.health,
+            length: i64::try_from(snake.body.len()).unwrap_or(i64::MAX),
             center_dist,
             dead: false,
             death_kind: DeathKind::Normal,
             remaining_opponents,
             available_squares,
+            territory: territory.squares,
+            nearest_food: territory.nearest_food,
             multisnake,
+            constrictor,
         }
     }
 
+    #[must_use]
     pub const fn dead(
-        snake_id: SnakeID,
+        snake: &Snake,
         death_kind: DeathKind,
         multisnake: bool,
     ) -> Self {
         Self {
-            snake_id,
+            snake_id: snake.id,
+            squad: snake.squad,
             health: 0,
             length: 0,
             center_dist: 0,
@@ -66,33 +165,126 @@ impl ScoreFactors {
             death_kind,
             remaining_opponents: 0,
             available_squares: 0,
+            territory: 0,
+            nearest_food: None,
             multisnake,
+            constrictor: false,
         }
     }
 
-    pub fn calculate(&self, depth: u64) -> i64 {
+    /// Whether this score belongs to `snake` or one of its team-mates.
+    #[must_use]
+    pub fn is_team_of(&self, snake: &Snake) -> bool {
+        self.snake_id == snake.id
+            || self.squad.is_some() && self.squad == snake.squad
+    }
+
+    #[must_use]
+    pub fn calculate(&self, depth: u64, weights: &Weights) -> i64 {
         let depth = i64::try_from(depth).unwrap_or(i64::MAX);
         if self.dead {
             // die as late as possible
             match self.death_kind {
-                DeathKind::Normal => -100_000_000 + depth * Self::DEPTH_WEIGHT,
-                DeathKind::Honourable => {
-                    -50_000_000 + depth * Self::DEPTH_WEIGHT
-                },
+                DeathKind::Normal => -100_000_000 + depth * weights.depth,
+                DeathKind::Honourable => -50_000_000 + depth * weights.depth,
             }
         } else if self.remaining_opponents == 0 && self.multisnake {
-            // win as early as possible
-            10_000_000 - depth * Self::DEPTH_WEIGHT
+            // win as early as possible. in squad games this includes
+            // winning alongside our team-mates.
+            10_000_000 - depth * weights.depth
         } else {
-            // otherwise, try to stay alive
-            self.health * Self::HEALTH_WEIGHT
-                + self.length * Self::LENGTH_WEIGHT
-                - self.center_dist * Self::CENTER_DIST_WEIGHT
-                - self.remaining_opponents * Self::REMAINING_OPPONENTS_WEIGHT
-                + self.available_squares * Self::AVAILABLE_SQUARES_WEIGHT
-                + depth * Self::DEPTH_WEIGHT
+            // otherwise, try to stay alive. in constrictor games everyone is
+            // always full and always growing, so health and length tell us
+            // nothing.
+            let sustenance = if self.constrictor {
+                0
+            } else {
+                self.health * weights.health
+                    + self.length * weights.length
+                    + self.appetite(weights)
+            };
+
+            sustenance
+                - self.center_dist * weights.center_dist
+                - self.remaining_opponents * weights.remaining_opponents
+                + self.available_squares * weights.available_squares
+                + self.territory * weights.territory
+                + depth * weights.depth
+        }
+    }
+
+    /// How much being close to food is worth. Food we can't get to before
+    /// anyone else is worth nothing, so letting it go is never better.
+    ///
+    /// If we'd run out of health before getting to any food we're as good as
+    /// dead, though that's still better than actually being dead.
+    fn appetite(&self, weights: &Weights) -> i64 {
+        match self.nearest_food {
+            Some(distance) if self.health < distance => -weights.starvation,
+            Some(distance) => weights.food / distance.max(1),
+            None => 0,
+        }
+    }
+}
+
+/// the most snakes a game can have and still be scored.
+pub const MAX_SNAKES: usize = 8;
+
+/// A score for each snake, kept by id. Scores get copied around a lot during
+/// the search, so they live in a fixed-size array instead of on the heap.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Scores([Option<ScoreFactors>; MAX_SNAKES]);
+
+impl Scores {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self([None; MAX_SNAKES])
+    }
+
+    #[must_use]
+    pub fn get(&self, snake_id: SnakeID) -> Option<&ScoreFactors> {
+        self.0.get(snake_id).and_then(Option::as_ref)
+    }
+
+    /// Puts `score` in for its snake, replacing whatever was there. Snakes
+    /// with ids too big to fit are left out, though [`MAX_SNAKES`] is checked
+    /// long before that could happen.
+    pub fn insert(&mut self, score: ScoreFactors) {
+        if let Some(slot) = self.0.get_mut(score.snake_id) {
+            *slot = Some(score);
         }
     }
+
+    /// Puts `score` in for its snake, unless that snake already has one.
+    pub fn or_insert(&mut self, score: ScoreFactors) {
+        if self.get(score.snake_id).is_none() {
+            self.insert(score);
+        }
+    }
+
+    /// Every score there is, in order of snake id.
+    pub fn iter(&self) -> impl Iterator<Item = &ScoreFactors> {
+        self.0.iter().flatten()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Index<SnakeID> for Scores {
+    type Output = ScoreFactors;
+
+    fn index(&self, snake_id: SnakeID) -> &ScoreFactors {
+        self.get(snake_id)
+            .unwrap_or_else(|| panic!("snake #{snake_id} has no score"))
+    }
 }
 
 impl fmt::Display for ScoreFactors {
@@ -103,14 +295,58 @@ impl fmt::Display for ScoreFactors {
             write!(
                 f,
                 "snake {}:\n* {} health\n* {} length\n* {} turns from \
-                 center\n* {} remaining opponents\n* {} available squares",
+                 center\n* {} remaining opponents\n* {} available squares\n* \
+                 {} squares of territory\n* {:?} moves from food",
                 self.snake_id,
                 self.health,
                 self.length,
                 self.center_dist,
                 self.remaining_opponents,
                 self.available_squares,
+                self.territory,
+                self.nearest_food,
             )
         }
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::{ScoreFactors, Territory, Weights};
+    use crate::{fightsnake::types::Coord, strategies::strangle::snake::Snake};
+
+    fn score(health: i64, nearest_food: Option<i64>) -> i64 {
+        let snake =
+            Snake::new(0, [Coord { x: 0, y: 0 }; 3].into(), health, None);
+        let territory = Territory {
+            squares: 10,
+            nearest_food,
+        };
+        ScoreFactors::alive(&snake, 5, 1, 10, territory, true, false)
+            .calculate(0, &Weights::DEFAULT)
+    }
+
+    #[test]
+    fn closer_food_is_better_than_none() {
+        assert!(score(50, Some(2)) > score(50, Some(8)));
+        assert!(score(50, Some(8)) > score(50, None));
+    }
+
+    #[test]
+    fn weights_only_need_to_mention_what_they_change() {
+        let weights: Weights =
+            serde_json::from_str(r#"{"territory": 5}"#).expect("bad weights");
+        assert_eq!(
+            weights,
+            Weights {
+                territory: 5,
+                ..Weights::DEFAULT
+            }
+        );
+        assert_eq!(Weights::DEFAULT.fingerprint(), "default");
+        assert_ne!(weights.fingerprint(), "default");
+
+        assert!(serde_json::from_str::<Weights>(r#"{"teritory": 5}"#).is_err());
+    }
+}
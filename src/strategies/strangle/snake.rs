@@ -3,7 +3,12 @@ use std::{
     hash::{Hash, Hasher},
 };
 
-use super::{board::Board, SnakeID};
+use super::{
+    board::Board,
+    zobrist::{self, Key},
+    SnakeID,
+    SquadID,
+};
 use crate::fightsnake::types::{Coord, Direction};
 
 #[derive(Clone, Debug, Eq)]
@@ -11,23 +16,138 @@ pub struct Snake {
     pub id:     SnakeID,
     pub body:   VecDeque<Coord>,
     pub health: i64,
+    pub squad:  Option<SquadID>,
+    /// this snake's share of the position's zobrist key. kept up to date by
+    /// the methods that move, feed and grow the snake.
+    key:        Key,
+}
+
+/// What a snake was like before a turn, so that the turn can be taken back.
+#[derive(Clone, Copy, Debug)]
+pub struct Saved {
+    tail:   Option<Coord>,
+    length: usize,
+    health: i64,
+    key:    Key,
 }
 
 impl Snake {
-    pub fn facing(&self) -> Option<Direction> {
-        Direction::between(self.body[1], self.body[0])
+    pub fn new(
+        id: SnakeID,
+        body: VecDeque<Coord>,
+        health: i64,
+        squad: Option<SquadID>,
+    ) -> Self {
+        let key = body
+            .iter()
+            .map(|&c| zobrist::body(id, c))
+            .chain(body.front().map(|&c| zobrist::head(id, c)))
+            .fold(zobrist::health(id, health), Key::wrapping_add);
+
+        Self {
+            id,
+            body,
+            health,
+            squad,
+            key,
+        }
+    }
+
+    pub const fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Moves our head to `head`, and drags the rest of the body along behind
+    /// it.
+    pub fn slither(&mut self, head: Coord) {
+        if let Some(tail) = self.body.pop_back() {
+            self.key = self.key.wrapping_sub(zobrist::body(self.id, tail));
+        }
+        if let Some(&old) = self.body.front() {
+            self.key = self.key.wrapping_sub(zobrist::head(self.id, old));
+        }
+        self.body.push_front(head);
+        self.key = self
+            .key
+            .wrapping_add(zobrist::body(self.id, head))
+            .wrapping_add(zobrist::head(self.id, head));
+    }
+
+    pub fn set_health(&mut self, health: i64) {
+        self.key = self
+            .key
+            .wrapping_sub(zobrist::health(self.id, self.health))
+            .wrapping_add(zobrist::health(self.id, health));
+        self.health = health;
+    }
+
+    /// Grows or shrinks to `length` by stacking or removing tail segments.
+    pub fn set_length(&mut self, length: usize) {
+        while self.body.len() > length.max(1) {
+            if let Some(tail) = self.body.pop_back() {
+                self.key = self.key.wrapping_sub(zobrist::body(self.id, tail));
+            }
+        }
+        while self.body.len() < length {
+            self.grow();
+        }
+    }
+
+    /// Stacks a new segment on our tail, like after eating.
+    pub fn grow(&mut self) {
+        if let Some(&tail) = self.body.back() {
+            self.body.push_back(tail);
+            self.key = self.key.wrapping_add(zobrist::body(self.id, tail));
+        }
+    }
+
+    pub fn save(&self) -> Saved {
+        Saved {
+            tail:   self.body.back().copied(),
+            length: self.body.len(),
+            health: self.health,
+            key:    self.key,
+        }
+    }
+
+    /// Puts the snake back how it was when `saved` was made. The snake must
+    /// have slithered exactly once since then, and only grown afterwards.
+    pub fn restore(&mut self, saved: Saved) {
+        self.body.pop_front();
+        self.body.truncate(saved.length.saturating_sub(1));
+        self.body.extend(saved.tail);
+        self.health = saved.health;
+        self.key = saved.key;
+    }
+
+    pub fn facing(&self, board: &Board) -> Option<Direction> {
+        // going through the board means this still works when our neck is on
+        // the other side of a wrapped edge.
+        Direction::iter()
+            .copied()
+            .find(|d| board.neighbour(self.body[1], *d) == self.body[0])
+    }
+
+    /// Whether we're on the same team as `other`. Snakes without a squad are
+    /// a team of one.
+    pub fn is_ally(&self, other: &Self) -> bool {
+        self.id == other.id || self.squad.is_some() && self.squad == other.squad
+    }
+
+    /// Whether moving in `direction` keeps us on the board and out of our own
+    /// neck.
+    pub fn can_move(&self, board: &Board, direction: Direction) -> bool {
+        let square = board.neighbour(self.body[0], direction);
+        // a neck stacked under the head, like at the start of a game, doesn't
+        // rule anything out.
+        board.contains(square) && self.body.get(1) != Some(&square)
     }
 
+    /// Every direction that [`Self::can_move`] allows.
     pub fn possible_directions(&self, board: &Board) -> Vec<Direction> {
         Direction::iter()
             .copied()
-            .filter(|d| {
-                if let Some(facing) = self.facing() && facing.opposite() == *d {
-                    // filter out our neck
-                    return false;
-                }
-                board.contains(self.body[0].neighbour(*d))
-            })
+            .filter(|&d| self.can_move(board, d))
             .collect()
     }
 }
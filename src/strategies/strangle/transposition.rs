@@ -0,0 +1,153 @@
+use std::sync::{
+    atomic::{AtomicU32, AtomicU64, Ordering},
+    Mutex,
+    PoisonError,
+};
+
+use super::{score_factors::Scores, zobrist::Key};
+
+/// Leaf scores we've already worked out, kept in a fixed amount of memory.
+///
+/// Leaf scores only depend on the position, so a table can be kept for a
+/// whole game and reused from one turn to the next. Each bucket has its own
+/// lock, so any number of search threads can share one table.
+pub struct TranspositionTable {
+    /// pairs of slots. a position can only ever live in the bucket its key
+    /// picks.
+    buckets:    Vec<Mutex<[Option<Entry>; 2]>>,
+    /// which search the table is being used for, so entries from earlier
+    /// turns can be told apart from fresh ones.
+    generation: AtomicU32,
+    /// how many lookups found a score during the current search.
+    hits:       AtomicU64,
+    /// how many lookups there have been during the current search.
+    probes:     AtomicU64,
+}
+
+struct Entry {
+    /// the high half of the zobrist key, to make sure a hit really is for the
+    /// same position.
+    check:      u64,
+    generation: u32,
+    scores:     Scores,
+}
+
+impl TranspositionTable {
+    /// how many positions a table holds if nobody asks for anything else.
+    pub const DEFAULT_CAPACITY: usize = 1 << 15;
+
+    /// Makes a table that holds at most `capacity` positions.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buckets:    (0..(capacity / 2).max(1))
+                .map(|_| Mutex::new([None, None]))
+                .collect(),
+            generation: AtomicU32::new(0),
+            hits:       AtomicU64::new(0),
+            probes:     AtomicU64::new(0),
+        }
+    }
+
+    /// Marks the start of a new search. Everything already in the table can
+    /// still be found, but is the first to go when space runs out.
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        self.hits.store(0, Ordering::Relaxed);
+        self.probes.store(0, Ordering::Relaxed);
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn probes(&self) -> u64 {
+        self.probes.load(Ordering::Relaxed)
+    }
+
+    #[allow(clippy::cast_possible_truncation)] // splitting the key in half
+    fn locate(&self, key: Key) -> (&Mutex<[Option<Entry>; 2]>, u64) {
+        let index = key as u64 % self.buckets.len() as u64;
+        (&self.buckets[index as usize], (key >> 64) as u64)
+    }
+
+    pub fn get(&self, key: Key) -> Option<Scores> {
+        let (bucket, check) = self.locate(key);
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.probes.fetch_add(1, Ordering::Relaxed);
+
+        let scores = bucket
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter_mut()
+            .flatten()
+            .find(|entry| entry.check == check)
+            .map(|entry| {
+                // anything we're still using is worth keeping around.
+                entry.generation = generation;
+                entry.scores
+            })?;
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Some(scores)
+    }
+
+    /// Stores the scores for a position.
+    ///
+    /// The position goes in an empty slot of its bucket if there is one.
+    /// Otherwise it replaces whichever entry was used least recently, with
+    /// ties going to the second slot so the first keeps hold of older work.
+    pub fn insert(&self, key: Key, scores: Scores) {
+        let (bucket, check) = self.locate(key);
+        let generation = self.generation.load(Ordering::Relaxed);
+        let age = |slot: &Option<Entry>| {
+            slot.as_ref().map_or(0, |existing| {
+                generation.wrapping_sub(existing.generation)
+            })
+        };
+
+        let mut bucket = bucket.lock().unwrap_or_else(PoisonError::into_inner);
+        let slot = bucket
+            .iter()
+            .position(|slot| {
+                slot.as_ref().is_none_or(|existing| existing.check == check)
+            })
+            .unwrap_or_else(|| usize::from(age(&bucket[0]) <= age(&bucket[1])));
+        bucket[slot] = Some(Entry {
+            check,
+            generation,
+            scores,
+        });
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TranspositionTable;
+    use crate::strategies::strangle::score_factors::Scores;
+
+    #[test]
+    fn lookups_check_the_whole_key() {
+        let table = TranspositionTable::new(2);
+        table.insert(1, Scores::new());
+        assert!(table.get(1 | 1 << 64).is_none());
+        assert!(table.get(1).is_some());
+    }
+
+    #[test]
+    fn old_entries_are_replaced_first() {
+        let table = TranspositionTable::new(2);
+        table.insert(1 << 64, Scores::new());
+        table.new_search();
+        table.insert(2 << 64, Scores::new());
+        table.insert(3 << 64, Scores::new());
+        assert!(table.get(1 << 64).is_none());
+        assert!(table.get(2 << 64).is_some());
+        assert!(table.get(3 << 64).is_some());
+    }
+}
@@ -0,0 +1,74 @@
+use super::{score_factors::DeathKind, SnakeID};
+use crate::fightsnake::types::Coord;
+
+/// A zobrist key for a position.
+///
+/// The keys for each feature of a position are added together rather than
+/// xored, so that stacked body segments don't cancel each other out. Keys are
+/// 128 bits wide so that half can pick a transposition table entry, and the
+/// other half can check that the entry really is for the same position.
+pub type Key = u128;
+
+#[derive(Clone, Copy)]
+enum Feature {
+    Body = 1,
+    Head,
+    Health,
+    Food,
+    Hazard,
+    Death,
+}
+
+/// splitmix64, which turns any input into a well mixed random-looking number.
+const fn splitmix(seed: u64) -> u64 {
+    let mut x = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// The random key for one feature of a position. Mixing the feature up on the
+/// fly saves keeping a table of random numbers for every board size.
+fn key(feature: Feature, snake: u64, value: u64) -> Key {
+    let seed = (feature as u64) << 56 ^ snake << 48 ^ value;
+    u128::from(splitmix(seed)) << 64
+        | u128::from(splitmix(seed ^ 0x5851_F42D_4C95_7F2D))
+}
+
+/// Packs a coordinate into 48 bits. Only the bits matter, so coordinates off
+/// the edge of the board are fine.
+#[allow(clippy::cast_sign_loss)]
+const fn coord(c: Coord) -> u64 {
+    (c.x as u64 & 0xFF_FFFF) << 24 | c.y as u64 & 0xFF_FFFF
+}
+
+/// One segment of a snake's body.
+pub fn body(snake: SnakeID, c: Coord) -> Key {
+    key(Feature::Body, snake as u64, coord(c))
+}
+
+/// Where a snake's head is.
+pub fn head(snake: SnakeID, c: Coord) -> Key {
+    key(Feature::Head, snake as u64, coord(c))
+}
+
+/// How much health a snake has.
+#[allow(clippy::cast_sign_loss)]
+pub fn health(snake: SnakeID, health: i64) -> Key {
+    key(Feature::Health, snake as u64, health as u64)
+}
+
+/// A piece of food.
+pub fn food(c: Coord) -> Key {
+    key(Feature::Food, 0, coord(c))
+}
+
+/// A hazard square. Stacked hazards add up just like body segments.
+pub fn hazard(c: Coord) -> Key {
+    key(Feature::Hazard, 0, coord(c))
+}
+
+/// A snake that died in the last turn, and how it died.
+pub fn death(snake: SnakeID, death_kind: DeathKind) -> Key {
+    key(Feature::Death, snake as u64, death_kind as u64)
+}
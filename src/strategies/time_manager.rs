@@ -0,0 +1,183 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use crate::fightsnake::models::GameState;
+
+/// Tracks how much of each game's timeout is eaten by the network, and uses
+/// that to decide how long the search is allowed to think for.
+pub struct TimeManager {
+    min_margin: Duration,
+    games:      Mutex<HashMap<String, GameTiming>>,
+}
+
+#[derive(Default)]
+struct GameTiming {
+    /// how long we spent thinking on the previous turn.
+    last_thinking: Option<Duration>,
+    /// smoothed estimate of the time lost outside of our own thinking.
+    overhead:      Option<Duration>,
+}
+
+impl GameTiming {
+    /// weight given to the newest overhead observation, out of 4.
+    const SMOOTHING: u32 = 1;
+
+    /// Folds the latency the engine reported for our last response into the
+    /// overhead estimate, and returns the new estimate.
+    fn observe(&mut self, latency: Duration) -> Option<Duration> {
+        if let Some(thinking) = self.last_thinking
+            && latency > Duration::ZERO
+        {
+            let observed = latency.saturating_sub(thinking);
+            self.overhead = Some(self.overhead.map_or(observed, |old| {
+                (old * (4 - Self::SMOOTHING) + observed * Self::SMOOTHING) / 4
+            }));
+        }
+        self.overhead
+    }
+}
+
+impl TimeManager {
+    /// the smallest amount of time we will ever give the search.
+    const MIN_BUDGET: Duration = Duration::from_millis(10);
+    /// how many times the observed overhead we leave spare.
+    const OVERHEAD_FACTOR: u32 = 2;
+
+    #[must_use]
+    pub fn new(min_margin: Duration) -> Self {
+        Self {
+            min_margin,
+            games: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Works out how long the search may run for on this turn.
+    ///
+    /// The snake's reported latency is the round trip the engine measured for
+    /// our previous response, so anything above the time we spent thinking is
+    /// network overhead.
+    pub fn budget(&self, game_state: &GameState) -> Duration {
+        let overhead = self
+            .games
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(game_state.game.id.clone())
+            .or_default()
+            .observe(Duration::from_millis(game_state.you.latency));
+
+        let margin = overhead.map_or(self.min_margin, |overhead| {
+            self.min_margin.max(overhead * Self::OVERHEAD_FACTOR)
+        });
+
+        Duration::from_millis(game_state.game.timeout)
+            .saturating_sub(margin)
+            .max(Self::MIN_BUDGET)
+    }
+
+    /// Records how long we spent thinking, so the next turn's latency can be
+    /// split into thinking time and network time.
+    pub fn finish(&self, game_id: &str, thinking: Duration) {
+        let mut games =
+            self.games.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(timing) = games.get_mut(game_id) {
+            timing.last_thinking = Some(thinking);
+        }
+    }
+
+    /// Forgets everything we know about a game.
+    pub fn end(&self, game_id: &str) {
+        self.games
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(game_id);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use std::time::Duration;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::TimeManager;
+    use crate::{
+        arena::{new_game, ArenaOptions},
+        fightsnake::models::GameState,
+    };
+
+    const MARGIN: Duration = Duration::from_millis(100);
+
+    /// A game with a 500ms timeout, where our last answer took `latency`
+    /// milliseconds to arrive.
+    fn turn(latency: u64) -> GameState {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut state =
+            new_game(1, &ArenaOptions::default(), &mut rng).expect("no game");
+        state.game.timeout = 500;
+        state.you.latency = latency;
+        state
+    }
+
+    #[test]
+    fn the_first_turn_leaves_the_minimum_margin() {
+        let manager = TimeManager::new(MARGIN);
+        assert_eq!(manager.budget(&turn(0)), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn slow_turns_shrink_the_budget() {
+        let manager = TimeManager::new(MARGIN);
+        manager.budget(&turn(0));
+        manager.finish("arena-0", Duration::from_millis(300));
+
+        // 200ms went missing on the way, and twice that is left spare.
+        assert_eq!(manager.budget(&turn(500)), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn overheads_are_averaged_out() {
+        let manager = TimeManager::new(MARGIN);
+        manager.budget(&turn(0));
+        manager.finish("arena-0", Duration::from_millis(100));
+        // 200ms of overhead, so 400ms of margin.
+        assert_eq!(manager.budget(&turn(300)), Duration::from_millis(100));
+        manager.finish("arena-0", Duration::from_millis(100));
+
+        // one quick turn only brings the estimate down a quarter of the way.
+        assert_eq!(manager.budget(&turn(100)), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn missing_latencies_are_ignored() {
+        let manager = TimeManager::new(MARGIN);
+        manager.budget(&turn(0));
+        manager.finish("arena-0", Duration::from_millis(300));
+        assert_eq!(manager.budget(&turn(0)), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn the_budget_never_drops_below_the_floor() {
+        let manager = TimeManager::new(MARGIN);
+        manager.budget(&turn(0));
+        manager.finish("arena-0", Duration::from_millis(100));
+        assert_eq!(
+            manager.budget(&turn(2000)),
+            TimeManager::MIN_BUDGET,
+            "1900ms of overhead should leave nothing to think with"
+        );
+    }
+
+    #[test]
+    fn forgotten_games_start_again() {
+        let manager = TimeManager::new(MARGIN);
+        manager.budget(&turn(0));
+        manager.finish("arena-0", Duration::from_millis(100));
+        manager.budget(&turn(2000));
+        manager.end("arena-0");
+        assert_eq!(manager.budget(&turn(2000)), Duration::from_millis(400));
+    }
+}